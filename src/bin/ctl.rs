@@ -3,7 +3,8 @@ use promptcmd::{cmd, ENV_CONFIG};
 use promptcmd::cmd::BasicTextEditor;
 use promptcmd::config::appconfig::AppConfig;
 use promptcmd::config::{self, appconfig_locator, RUNNER_BIN_NAME};
-use promptcmd::executor::Executor;
+use promptcmd::dotprompt::helpers::{open_terminal, SharedStdin};
+use promptcmd::executor::{CallChain, Executor};
 use promptcmd::lb::WeightedLoadBalancer;
 use promptcmd::stats::rusqlite_store::RusqliteStore;
 use std::env;
@@ -63,6 +64,15 @@ enum Commands {
 
     #[clap(about = "Render prompts without API calls")]
     Render(cmd::render::RenderCmd),
+
+    #[clap(about = "Run the test cases of prompts without API calls")]
+    Test(cmd::test::TestCmd),
+
+    #[clap(about = "Check prompt files for problems")]
+    Lint(cmd::lint::LintCmd),
+
+    #[clap(about = "Print the tree of prompts executed by a run")]
+    Trace(cmd::trace::TraceCmd),
 }
 
 static PROMPTS_STORAGE: OnceLock<FileSystemPromptFilesStorage> = OnceLock::new();
@@ -145,7 +155,9 @@ async fn main() -> Result<()> {
                 &mut installer,
                 &editor,
                 appconfig),
-        Commands::List(cmd) => cmd.exec(prompts_storage),
+        Commands::List(cmd) => cmd.exec(
+                prompts_storage,
+                &mut std::io::stdout()),
         Commands::Cat(cmd) => cmd.exec(
                 prompts_storage,
                 &mut std::io::stdout()),
@@ -157,7 +169,10 @@ async fn main() -> Result<()> {
                     loadbalancer: lb,
                     appconfig,
                     statsstore,
-                    prompts_storage
+                    prompts_storage,
+                    stdin: Arc::new(SharedStdin::from_stdin(appconfig.inputs.max_stdin_size)),
+                    terminal: open_terminal(cmd.no_input),
+                    chain: CallChain::default()
                 };
                 let executor_arc = Arc::new(executor);
                 cmd.exec(
@@ -185,7 +200,21 @@ async fn main() -> Result<()> {
         Commands::Render(cmd) => cmd.exec(
                 prompts_storage,
                 &mut std::io::stdout(),
-                &editor
+                &editor,
+                appconfig
+            ),
+        Commands::Test(cmd) => cmd.exec(
+                prompts_storage,
+                &mut std::io::stdout(),
+                appconfig
+            ),
+        Commands::Lint(cmd) => cmd.exec(
+                prompts_storage,
+                &mut std::io::stdout()
+            ),
+        Commands::Trace(cmd) => cmd.exec(
+                statsstore,
+                &mut std::io::stdout()
             ),
     }
 }
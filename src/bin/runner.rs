@@ -4,8 +4,11 @@ use promptcmd::config::{self, appconfig_locator};
 use promptcmd::config::appconfig::{AppConfig, GlobalProviderProperties};
 use promptcmd::cmd::run;
 use promptcmd::dotprompt::renderers::argmatches::DotPromptArgMatches;
-use promptcmd::dotprompt::DotPrompt;
-use promptcmd::executor::{ExecutionOutput, Executor, PromptInputs};
+use promptcmd::dotprompt::renderers::interactive;
+use promptcmd::dotprompt::diagnostic::Diagnostic;
+use promptcmd::dotprompt::{DotPrompt, ParseError};
+use promptcmd::dotprompt::helpers::{open_terminal, SharedStdin};
+use promptcmd::executor::{CallChain, ExecutionOutput, Executor, PromptInputs};
 use promptcmd::lb::WeightedLoadBalancer;
 use promptcmd::stats::rusqlite_store::{RusqliteStore};
 use promptcmd::storage::promptfiles_fs::{FileSystemPromptFilesStorage};
@@ -13,6 +16,7 @@ use promptcmd::storage::PromptFilesStorage;
 use promptcmd::ENV_CONFIG;
 use std::sync::{Arc};
 use std::{env};
+use std::ffi::OsString;
 use anyhow::{Context, Result, anyhow, bail};
 use std::path::PathBuf;
 use std::fs;
@@ -101,16 +105,24 @@ async fn main() -> Result<()> {
     debug!("Prompt name: {promptname}");
     // Check if loading by path (this handles also shebangs)
     let path = PathBuf::from(&promptname);
-    let promptdata = if path.exists() {
+    let (promptpath, promptdata) = if path.exists() {
         debug!("Reading prompt from file: {}", promptname);
-        fs::read_to_string(path)?
+        (promptname.clone(), fs::read_to_string(path)?)
     } else {
         debug!("Reading prompt from storage");
-        prompts_storage.load(&promptname)?.1
+        prompts_storage.load(&promptname)?
     };
 
-    let dotprompt: DotPrompt = DotPrompt::try_from((promptname.as_str(), promptdata.as_str()))?;
+    let dotprompt: DotPrompt = DotPrompt::try_from_extended(promptname.as_str(), promptdata.as_str(),
+        |parent| anyhow::Ok(prompts_storage.load(parent)?.1))
+        .map_err(|err| match err.downcast::<ParseError>() {
+            Ok(err) => anyhow!(Diagnostic::extended(err, Some(&promptpath), &promptdata,
+                |parent| prompts_storage.load(parent).ok())),
+            Err(err) => err
+        })?
+        .with_partials(prompts_storage.partials()?);
 
+    command = run::describe_command(command, &dotprompt);
     command = command.disable_help_flag(true);
     command = command.next_help_heading("Prompt inputs");
     command = run::generate_arguments_from_dotprompt(command, &dotprompt)?;
@@ -129,6 +141,18 @@ async fn main() -> Result<()> {
             .action(clap::ArgAction::SetTrue)
             .required(false)
         )
+        .arg(Arg::new("interactive")
+            .long("interactive")
+            .help("Ask for missing required inputs")
+            .action(clap::ArgAction::SetTrue)
+            .required(false)
+        )
+        .arg(Arg::new("no_input")
+            .long("no-input")
+            .help("Never ask for input, answer questions with their defaults")
+            .action(clap::ArgAction::SetTrue)
+            .required(false)
+        )
         .arg(
             Arg::new("help")
             .long("help")
@@ -168,7 +192,14 @@ async fn main() -> Result<()> {
         )
         ;
 
-    let matches = command.get_matches();
+    let mut params: Vec<OsString> = env::args_os().collect();
+    if let Some(relaxed) = interactive::relaxed_matches(&command, &params)
+        && (dotprompt.frontmatter.interactive || relaxed.get_flag("interactive"))
+        && let Some(terminal) = open_terminal(relaxed.get_flag("no_input")) {
+        params = interactive::ask_missing_inputs(&command, &dotprompt, &relaxed, params, terminal.as_ref())?;
+    }
+
+    let matches = command.get_matches_from(params);
 
     let lb = WeightedLoadBalancer {
         stats: statsstore
@@ -177,7 +208,10 @@ async fn main() -> Result<()> {
         loadbalancer: lb,
         appconfig,
         statsstore,
-        prompts_storage
+        prompts_storage,
+        stdin: Arc::new(SharedStdin::from_stdin(appconfig.inputs.max_stdin_size)),
+        terminal: open_terminal(matches.get_flag("no_input")),
+        chain: CallChain::default()
     };
 
     let arc_executor = Arc::new(executor);
@@ -209,7 +243,8 @@ async fn main() -> Result<()> {
 
     let argmatches = DotPromptArgMatches {
         matches,
-        dotprompt: &dotprompt
+        dotprompt: &dotprompt,
+        config: &appconfig.inputs
     };
 
     let inputs: PromptInputs = argmatches.try_into()?;
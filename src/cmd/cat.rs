@@ -2,6 +2,7 @@ use std::io::Write;
 
 use clap::{Parser};
 use anyhow::{Result, bail};
+use crate::dotprompt::{DotPrompt, Metadata};
 use crate::{storage::PromptFilesStorage};
 
 
@@ -9,6 +10,32 @@ use crate::{storage::PromptFilesStorage};
 pub struct CatCmd {
     #[arg()]
     pub promptname: String,
+
+    #[arg(short, long, help="Print the prompt's metadata instead of its content")]
+    pub info: bool,
+}
+
+fn write_metadata(out: &mut impl Write, promptname: &str, metadata: &Metadata) -> Result<()> {
+    writeln!(out, "Name: {promptname}")?;
+    if let Some(description) = metadata.description.as_ref() {
+        writeln!(out, "Description: {description}")?;
+    }
+    if let Some(version) = metadata.version.as_ref() {
+        writeln!(out, "Version: {version}")?;
+    }
+    if let Some(author) = metadata.author.as_ref() {
+        writeln!(out, "Author: {author}")?;
+    }
+    if !metadata.tags.is_empty() {
+        writeln!(out, "Tags: {}", metadata.tags.join(", "))?;
+    }
+    if !metadata.examples.is_empty() {
+        writeln!(out, "Examples:")?;
+        for example in &metadata.examples {
+            writeln!(out, "  {example}")?;
+        }
+    }
+    Ok(())
 }
 
 impl CatCmd {
@@ -21,7 +48,13 @@ impl CatCmd {
 
         let promptdata = storage.load(&self.promptname)?.1;
 
-        writeln!(out, "{promptdata}")?;
+        if self.info {
+            let dotprompt = DotPrompt::try_from_extended(&self.promptname, &promptdata,
+                |parent| anyhow::Ok(storage.load(parent)?.1))?;
+            write_metadata(out, &self.promptname, &dotprompt.frontmatter.metadata)?;
+        } else {
+            writeln!(out, "{promptdata}")?;
+        }
 
         Ok(())
     }
@@ -42,7 +75,8 @@ mod tests {
         let mut buf = Vec::new();
 
         let cmd = CatCmd {
-            promptname: String::from("aaa")
+            promptname: String::from("aaa"),
+            info: false
         };
 
         cmd.exec(&storage, &mut buf).unwrap();
@@ -60,9 +94,42 @@ mod tests {
         let mut buf = Vec::new();
 
         let cmd = CatCmd {
-            promptname: String::from("aaa")
+            promptname: String::from("aaa"),
+            info: false
         };
 
         assert!(cmd.exec(&storage, &mut buf).is_err());
     }
+
+    #[test]
+    fn test_info() {
+        let storage = InMemoryPromptFilesStorage::default();
+        storage.store("summarize", r#"---
+description: Summarize text
+version: 1.2
+author: Jane
+tags: [writing, text]
+examples:
+  - summarize --words 10 < article.txt
+---
+Summarize"#).unwrap();
+
+        let mut buf = Vec::new();
+
+        let cmd = CatCmd {
+            promptname: String::from("summarize"),
+            info: true
+        };
+
+        cmd.exec(&storage, &mut buf).unwrap();
+
+        assert_eq!(String::from_utf8(buf).unwrap(), "Name: summarize
+Description: Summarize text
+Version: 1.2
+Author: Jane
+Tags: writing, text
+Examples:
+  summarize --words 10 < article.txt
+");
+    }
 }
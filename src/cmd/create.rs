@@ -10,6 +10,7 @@ use crate::config::appconfig::AppConfig;
 use crate::config::appconfig_locator;
 use crate::installer::DotPromptInstaller;
 use crate::storage::PromptFilesStorage;
+use crate::dotprompt::diagnostic::Diagnostic;
 use crate::{dotprompt::DotPrompt};
 use crate::config::resolver::{self, ResolvedGlobalProperties, ResolvedProperty, ResolvedPropertySource, Resolver};
 use crate::config::providers::{ModelInfo, error};
@@ -28,7 +29,7 @@ pub struct CreateCmd {
 }
 
 pub enum WriteResult {
-    Validated(DotPrompt, String),
+    Validated(Box<DotPrompt>, String),
     Written(String),
     Aborted,
     Edit
@@ -184,14 +185,16 @@ pub fn validate_and_write(
             Ok(dotprompt)
         },
         Err(err) => {
-            Err(err.to_string())
+            let path = storage.exists(promptname)
+                .unwrap_or_else(|| format!("{promptname}.prompt"));
+            Err(Diagnostic::new(err, Some(&path), promptdata).to_string())
         }
     };
 
     match validation_result {
         Ok(dotprompt) => {
             let path = storage.store(promptname, promptdata)?;
-            Ok(WriteResult::Validated(dotprompt, path))
+            Ok(WriteResult::Validated(Box::new(dotprompt), path))
         }
         Err(err) => {
             println!("{}", err);
@@ -61,7 +61,7 @@ impl EditCmd {
 
 #[cfg(test)]
 mod tests {
-    use crate::{cmd::{self, edit::EditCmd, TextEditor, TextEditorFileType}, config::appconfig::AppConfig, installer::tests::InMemoryInstaller, storage::{promptfiles_mem::InMemoryPromptFilesStorage, PromptFilesStorage}};
+    use crate::{cmd::{self, edit::EditCmd, TextEditor, TextEditorFileType}, storage::{promptfiles_mem::InMemoryPromptFilesStorage, PromptFilesStorage}};
 
     const PROMPTFILE_BASIC_VALID_1: &str = r#"
 ---
@@ -118,20 +118,14 @@ Basic Prompt Here: {{message}}
 
     struct TestState {
         storage: InMemoryPromptFilesStorage,
-        installer: InMemoryInstaller,
-        config: AppConfig,
         inp: Vec<u8>,
-        out: Vec<u8>,
         editor: TestingTextEditor
     }
 
     fn setup(inpdata: &[u8]) -> TestState {
         TestState {
             storage: InMemoryPromptFilesStorage::default(),
-            installer: InMemoryInstaller::default(),
-            config: AppConfig::default(),
             inp: inpdata.to_vec(),
-            out: Vec::new(),
             editor: TestingTextEditor::default()
         }
     }
@@ -162,7 +156,7 @@ Basic Prompt Here: {{message}}
 
     #[test]
     fn test_non_existent() {
-        let mut state = setup(b"");
+        let state = setup(b"");
         let promptname = String::from("myprompt");
 
         EditCmd {
@@ -35,6 +35,10 @@ impl EnableCmd {
         installer: &mut impl DotPromptInstaller,
         ) -> Result<()> {
 
+        if storage.exists(&self.promptname).is_none() && storage.list_partials()?.contains_key(&self.promptname) {
+            bail!("'{}' is a partial and can not be enabled as a command", &self.promptname);
+        }
+
         if let Some(path) = installer.is_installed(&self.promptname) {
             error!("Install path {} already exists", path);
             return Ok(());
@@ -139,4 +143,19 @@ mod tests {
 
         assert!(cmd.exec(&state.storage, &mut state.installer).is_ok());
     }
+
+    #[test]
+    fn test_enable_partial() {
+        let mut state = setup();
+
+        state.storage.store_partial("code-only", "Output only code");
+
+        let cmd = EnableCmd {
+            promptname: String::from("code-only")
+        };
+
+        let err = cmd.exec(&state.storage, &mut state.installer).unwrap_err();
+        assert!(err.to_string().contains("is a partial"));
+        assert!(state.installer.is_installed("code-only").is_none());
+    }
 }
@@ -0,0 +1,267 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::io::Write;
+use std::path::Path;
+
+use clap::{Parser};
+use anyhow::{bail, Result};
+use serde::Serialize;
+use crate::dotprompt::lint::{lint, template_line_offset, Finding, Severity};
+use crate::dotprompt::diagnostic::Diagnostic;
+use crate::dotprompt::{DotPrompt, ParseError};
+use crate::storage::PromptFilesStorage;
+
+#[derive(Parser)]
+pub struct LintCmd {
+    #[arg(help="Prompts to lint, all prompts if none given")]
+    pub promptnames: Vec<String>,
+
+    #[arg(long, help="Print findings as JSON")]
+    pub json: bool,
+}
+
+#[derive(Serialize)]
+struct PromptFinding {
+    prompt: String,
+    #[serde(flatten)]
+    finding: Finding,
+}
+
+fn parse(storage: &impl PromptFilesStorage, promptname: &str, partials: &HashMap<String, String>) -> Result<(String, DotPrompt)> {
+    let (_, promptdata) = storage.load(promptname)?;
+    let dotprompt = DotPrompt::try_from_extended(promptname, &promptdata,
+        |parent| anyhow::Ok(storage.load(parent)?.1))?
+        .with_partials(partials.clone());
+
+    Ok((promptdata, dotprompt))
+}
+
+fn on_path(binary: &str) -> bool {
+    let path = Path::new(binary);
+    if path.components().count() > 1 {
+        return path.is_file();
+    }
+
+    env::var_os("PATH").is_some_and(|paths| env::split_paths(&paths).any(|dir| {
+        let candidate = dir.join(binary);
+        candidate.is_file() || (cfg!(windows) && candidate.with_extension("exe").is_file())
+    }))
+}
+
+/// Follows the prompts referenced by nested prompts looking for a chain
+/// leading back to the first prompt of `chain`.
+fn find_cycle(
+    storage: &impl PromptFilesStorage,
+    partials: &HashMap<String, String>,
+    references: &mut HashMap<String, Vec<String>>,
+    chain: &mut Vec<String>,
+    visited: &mut HashSet<String>,
+) -> Option<Vec<String>> {
+    let current = chain.last()?.clone();
+    let targets = references.entry(current.clone()).or_insert_with(|| {
+        parse(storage, &current, partials)
+            .map(|(_, dotprompt)| lint(&dotprompt, None).prompts.into_iter().map(|reference| reference.name).collect())
+            .unwrap_or_default()
+    }).clone();
+
+    for target in targets {
+        if chain.first() == Some(&target) {
+            return Some([chain.clone(), vec![target]].concat());
+        }
+        if !visited.insert(target.clone()) {
+            continue;
+        }
+        chain.push(target);
+        if let Some(cycle) = find_cycle(storage, partials, references, chain, visited) {
+            return Some(cycle);
+        }
+        chain.pop();
+    }
+    None
+}
+
+impl LintCmd {
+    pub fn exec(&self,
+        storage: &impl PromptFilesStorage,
+        out: &mut impl Write,
+    )-> Result<()> {
+
+        let mut promptnames = if self.promptnames.is_empty() {
+            storage.list()?.into_keys().collect::<Vec<_>>()
+        } else {
+            self.promptnames.clone()
+        };
+        promptnames.sort();
+
+        let partials = storage.partials()?;
+        let mut references: HashMap<String, Vec<String>> = HashMap::new();
+        let mut findings: Vec<PromptFinding> = Vec::new();
+
+        for promptname in promptnames {
+            if storage.exists(&promptname).is_none() {
+                bail!("Could not find a prompt with the name \"{promptname}\"");
+            }
+
+            let mut push = |severity: Severity, message: String, line: Option<usize>, column: Option<usize>| {
+                findings.push(PromptFinding {
+                    prompt: promptname.clone(),
+                    finding: Finding { severity, message, line, column }
+                });
+            };
+
+            let (promptdata, dotprompt) = match parse(storage, &promptname, &partials) {
+                Ok(parsed) => parsed,
+                Err(err) => {
+                    match (err.downcast::<ParseError>(), storage.load(&promptname)) {
+                        (Ok(err), Ok((_, promptdata))) => {
+                            let diagnostic = Diagnostic::new(err, None, &promptdata);
+                            push(Severity::Error, diagnostic.message(), diagnostic.line, diagnostic.column);
+                        }
+                        (Ok(err), Err(_)) => push(Severity::Error, err.to_string(), None, None),
+                        (Err(err), _) => push(Severity::Error, err.to_string(), None, None),
+                    }
+                    continue;
+                }
+            };
+
+            // The template of a prompt extending another one comes from elsewhere
+            let line_offset = match dotprompt.frontmatter.extends {
+                Some(_) => None,
+                None => template_line_offset(&promptdata, &dotprompt.template)
+            };
+            let result = lint(&dotprompt, line_offset);
+
+            for finding in result.findings {
+                push(finding.severity, finding.message, finding.line, finding.column);
+            }
+
+            for prompt in &result.prompts {
+                if storage.exists(&prompt.name).is_none() {
+                    push(Severity::Error, format!("nested prompt '{}' does not exist", prompt.name), prompt.line, prompt.column);
+                }
+            }
+
+            for binary in result.binaries {
+                if !on_path(&binary.name) {
+                    push(Severity::Warning, format!("'{}' is not found in PATH", binary.name), binary.line, binary.column);
+                }
+            }
+
+            references.insert(promptname.clone(), result.prompts.into_iter().map(|reference| reference.name).collect());
+            let cycle = find_cycle(storage, &partials, &mut references,
+                &mut vec![promptname.clone()], &mut HashSet::new());
+            if let Some(cycle) = cycle {
+                push(Severity::Error, format!("nested prompts form a cycle: {}", cycle.join(" -> ")), None, None);
+            }
+        }
+
+        let errors = findings.iter().filter(|finding| finding.finding.severity == Severity::Error).count();
+        let warnings = findings.len() - errors;
+
+        if self.json {
+            writeln!(out, "{}", serde_json::to_string_pretty(&findings)?)?;
+        } else {
+            for PromptFinding { prompt, finding } in &findings {
+                let location = match (finding.line, finding.column) {
+                    (Some(line), Some(column)) => format!("{prompt}:{line}:{column}"),
+                    _ => prompt.clone()
+                };
+                let severity = match finding.severity {
+                    Severity::Error => "error",
+                    Severity::Warning => "warning"
+                };
+                writeln!(out, "{location}: {severity}: {}", finding.message)?;
+            }
+            writeln!(out, "{errors} error(s), {warnings} warning(s)")?;
+        }
+
+        if errors > 0 {
+            bail!("{errors} error(s) found");
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use crate::cmd::lint::LintCmd;
+    use crate::storage::promptfiles_mem::InMemoryPromptFilesStorage;
+    use crate::storage::PromptFilesStorage;
+
+    fn run(storage: &InMemoryPromptFilesStorage, promptnames: &[&str], json: bool) -> (bool, String) {
+        let cmd = LintCmd {
+            promptnames: promptnames.iter().map(|name| name.to_string()).collect(),
+            json
+        };
+        let mut buf = Vec::new();
+        let result = cmd.exec(storage, &mut buf);
+        (result.is_ok(), String::from_utf8(buf).unwrap())
+    }
+
+    #[test]
+    fn test_lint_clean() {
+        let storage = InMemoryPromptFilesStorage::default();
+        storage.store("summarize", "---\ninput:\n  schema:\n    text: string\n---\nSummarize {{text}}").unwrap();
+
+        assert_eq!(run(&storage, &[], false), (true, "0 error(s), 0 warning(s)\n".to_string()));
+    }
+
+    #[test]
+    fn test_lint_findings() {
+        let storage = InMemoryPromptFilesStorage::default();
+        storage.store("review", r#"---
+input:
+  schema:
+    code: string
+    lang: string
+---
+Review {{cod}}
+{{exec "definitely-not-a-binary"}}
+{{prompt "missing"}}"#).unwrap();
+        storage.store("broken", "---\ninput: [\n---\nHi").unwrap();
+
+        let (ok, output) = run(&storage, &[], false);
+        assert!(!ok);
+        assert_eq!(output, "broken:3:1: error: Error parsing frontmatter: did not find expected node content, while parsing a flow node
+review:7:8: error: variable 'cod' is not declared in the input schema
+review: warning: input 'code' is declared but never used
+review: warning: input 'lang' is declared but never used
+review:9:1: error: nested prompt 'missing' does not exist
+review:8:1: warning: 'definitely-not-a-binary' is not found in PATH
+3 error(s), 3 warning(s)
+");
+    }
+
+    #[test]
+    fn test_lint_cycle() {
+        let storage = InMemoryPromptFilesStorage::default();
+        storage.store("a", r#"{{prompt "b"}}"#).unwrap();
+        storage.store("b", r#"{{prompt "c"}}"#).unwrap();
+        storage.store("c", r#"{{prompt "a"}}"#).unwrap();
+        storage.store("d", r#"{{prompt "a"}}"#).unwrap();
+
+        let (ok, output) = run(&storage, &["a", "d"], false);
+        assert!(!ok);
+        assert_eq!(output, "a: error: nested prompts form a cycle: a -> b -> c -> a\n1 error(s), 0 warning(s)\n");
+    }
+
+    #[test]
+    fn test_lint_json() {
+        let storage = InMemoryPromptFilesStorage::default();
+        storage.store("greet", "Hello {{name}}").unwrap();
+
+        let (ok, output) = run(&storage, &["greet"], true);
+        assert!(!ok);
+        let findings: Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(findings, serde_json::json!([{
+            "prompt": "greet",
+            "severity": "error",
+            "message": "variable 'name' is not declared in the input schema",
+            "line": 1,
+            "column": 7
+        }]));
+    }
+}
@@ -1,7 +1,10 @@
+use std::io::Write;
+
 use clap::{Parser};
 use anyhow::{ Result};
 use prettytable::{row, Table};
 use prettytable::format;
+use crate::dotprompt::{DotPrompt, Metadata};
 use crate::storage::{PromptFilesStorage};
 
 
@@ -9,6 +12,21 @@ use crate::storage::{PromptFilesStorage};
 pub struct ListCmd {
     #[arg(short, long, help="Print in long format")]
     pub long: bool,
+
+    #[arg(short, long, help="List partials instead of prompts")]
+    pub partials: bool,
+
+    #[arg(short, long, conflicts_with="partials", help="Only list prompts with the given tag")]
+    pub tag: Option<String>,
+}
+
+// Metadata of a stored prompt, None if it can not be parsed
+fn load_metadata(storage: &impl PromptFilesStorage, promptname: &str) -> Option<Metadata> {
+    let (_, promptdata) = storage.load(promptname).ok()?;
+    let dotprompt = DotPrompt::try_from_extended(promptname, &promptdata,
+        |parent| anyhow::Ok(storage.load(parent)?.1)).ok()?;
+
+    Some(dotprompt.frontmatter.metadata)
 }
 
 impl ListCmd {
@@ -16,8 +34,29 @@ impl ListCmd {
     pub fn exec(
         &self,
         storage: &impl PromptFilesStorage,
+        out: &mut impl Write,
     ) -> Result<()> {
-        let prompts = storage.list()?;
+        let mut entries = if self.partials {
+            storage.list_partials()?
+                .into_iter()
+                .map(|(identifier, path)| (identifier, path, None))
+                .collect::<Vec<_>>()
+        } else {
+            // Parsing every prompt is only needed for descriptions and tags
+            let with_metadata = self.long || self.tag.is_some();
+            storage.list()?
+                .into_iter()
+                .map(|(identifier, path)| {
+                    let metadata = with_metadata.then(|| load_metadata(storage, &identifier)).flatten();
+                    (identifier, path, metadata)
+                })
+                .filter(|(_, _, metadata)| match self.tag.as_ref() {
+                    Some(tag) => metadata.as_ref().is_some_and(|metadata| metadata.tags.contains(tag)),
+                    None => true
+                })
+                .collect::<Vec<_>>()
+        };
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
 
         if self.long {
 
@@ -27,16 +66,22 @@ impl ListCmd {
                 .build();
             table.set_format(format);
 
-            for (identifier, path) in prompts {
-                table.add_row(row![identifier, path]);
+            for (identifier, path, metadata) in entries {
+                let description = metadata
+                    .and_then(|metadata| metadata.description)
+                    .unwrap_or_default();
+                table.add_row(row![identifier, description, path]);
             }
 
-            table.printstd();
+            table.print(out)?;
 
         } else {
-            let joined = prompts.keys().cloned().collect::<Vec<_>>().join(" ");
+            let joined = entries.into_iter()
+                .map(|(identifier, _, _)| identifier)
+                .collect::<Vec<_>>()
+                .join(" ");
             if !joined.is_empty() {
-                println!("{joined}");
+                writeln!(out, "{joined}")?;
             }
         }
 
@@ -45,4 +90,59 @@ impl ListCmd {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use crate::cmd::list::ListCmd;
+    use crate::storage::promptfiles_mem::InMemoryPromptFilesStorage;
+    use crate::storage::PromptFilesStorage;
 
+    fn setup() -> InMemoryPromptFilesStorage {
+        let storage = InMemoryPromptFilesStorage::default();
+        storage.store("summarize", "---\ndescription: Summarize text\ntags: [writing]\n---\nSummarize").unwrap();
+        storage.store("review", "---\ndescription: Review code\ntags: [code]\n---\nReview").unwrap();
+        storage.store("plain", "Just a template").unwrap();
+        storage
+    }
+
+    fn list(cmd: ListCmd, storage: &InMemoryPromptFilesStorage) -> String {
+        let mut buf = Vec::new();
+        cmd.exec(storage, &mut buf).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn test_list() {
+        let storage = setup();
+        let output = list(ListCmd { long: false, partials: false, tag: None }, &storage);
+        assert_eq!(output, "plain review summarize\n");
+    }
+
+    #[test]
+    fn test_list_long_with_description() {
+        let storage = setup();
+        let output = list(ListCmd { long: true, partials: false, tag: None }, &storage);
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("review") && lines[1].contains("Review code"));
+        assert!(lines[2].starts_with("summarize") && lines[2].contains("Summarize text"));
+    }
+
+    #[test]
+    fn test_list_by_tag() {
+        let storage = setup();
+        let output = list(ListCmd { long: false, partials: false, tag: Some("code".to_string()) }, &storage);
+        assert_eq!(output, "review\n");
+    }
+
+    #[test]
+    fn test_list_partials() {
+        let storage = setup();
+        storage.store_partial("code-only", "Only code");
+        let output = list(ListCmd { long: false, partials: true, tag: None }, &storage);
+        assert_eq!(output, "code-only\n");
+
+        assert!(ListCmd::try_parse_from(["list", "--partials", "--tag", "code"]).is_err());
+    }
+}
@@ -10,6 +10,9 @@ pub mod stats;
 pub mod resolve;
 pub mod config;
 pub mod render;
+pub mod test;
+pub mod lint;
+pub mod trace;
 
 mod templates;
 
@@ -1,14 +1,18 @@
 use clap::{Parser};
 use clap::{Arg, Command};
 use handlebars::HelperDef;
+use std::ffi::OsString;
 use std::collections::HashMap;
-use std::io::{BufReader, Write};
-use std::sync::{Mutex};
+use std::io::{Write};
+use std::sync::{Arc};
 use std::convert::TryFrom;
 use anyhow::{Context, Result};
 use thiserror::Error;
 use crate::cmd::{TextEditor, TextEditorFileType};
+use crate::config::appconfig::AppConfig;
 use crate::dotprompt::renderers::argmatches::DotPromptArgMatches;
+use crate::dotprompt::renderers::interactive;
+use crate::dotprompt::messages::PromptMessages;
 use crate::dotprompt::renderers::Render;
 use crate::executor::{PromptInputs};
 use crate::dotprompt::{ helpers, DotPrompt};
@@ -22,6 +26,12 @@ pub struct RenderCmd {
     #[arg(long, short, help="Edit" )]
     pub edit: bool,
 
+    #[arg(long, help="Ask for missing required inputs" )]
+    pub interactive: bool,
+
+    #[arg(long, help="Never ask for input, answer questions with their defaults" )]
+    pub no_input: bool,
+
     #[arg(trailing_var_arg = true)]
     pub prompt_args: Vec<String>,
 }
@@ -47,38 +57,53 @@ impl RenderCmd {
     pub fn exec(&self,
         storage: &impl PromptFilesStorage,
         out: &mut impl Write,
-        editor: &impl TextEditor
+        editor: &impl TextEditor,
+        appconfig: &AppConfig
     )-> Result<()> {
 
         let (_, data) = storage.load(&self.promptname)?;
-        let dotprompt = DotPrompt::try_from((self.promptname.as_str(), data.as_str()))?;
+        let dotprompt = DotPrompt::try_from_extended(self.promptname.as_str(), data.as_str(),
+            |parent| anyhow::Ok(storage.load(parent)?.1))?
+            .with_partials(storage.partials()?);
 
         let mut command: Command = Command::new(self.promptname.to_string());
 
         command = generate_arguments_from_dotprompt(command, &dotprompt)?;
 
-        let params = [vec!["--".to_string()], self.prompt_args.clone()].concat();
+        let mut params = [vec!["--".to_string()], self.prompt_args.clone()].concat()
+            .into_iter().map(OsString::from).collect::<Vec<_>>();
+        if let Some(relaxed) = interactive::relaxed_matches(&command, &params)
+            && (dotprompt.frontmatter.interactive || self.interactive)
+            && let Some(terminal) = helpers::open_terminal(self.no_input) {
+            params = interactive::ask_missing_inputs(&command, &dotprompt, &relaxed, params, terminal.as_ref())?;
+        }
         let matches = command.get_matches_from(params);
 
         let argmatches = DotPromptArgMatches {
             matches,
-            dotprompt: &dotprompt
+            dotprompt: &dotprompt,
+            config: &appconfig.inputs
         };
 
-        let inputs: PromptInputs = argmatches.try_into()?;
+        let mut inputs: PromptInputs = argmatches.try_into()?;
+
+        let stdin = Arc::new(helpers::SharedStdin::from_stdin(appconfig.inputs.max_stdin_size));
+        stdin.insert_has_stdin(&dotprompt, &mut inputs);
 
-        let exec_helper: Box<dyn HelperDef + Send + Sync> = Box::new(helpers::ExecHelper);
+        let exec_helper: Box<dyn HelperDef + Send + Sync> = Box::new(helpers::ExecHelper::default());
         let concat_helper: Box<dyn HelperDef + Send + Sync> = Box::new(helpers::ConcatHelper);
         let stdin_helper: Box<dyn HelperDef + Send + Sync> = Box::new(helpers::StdinHelper {
-            inp: Mutex::new(BufReader::new(std::io::stdin()))
+            stdin: stdin.clone()
         });
         let stdin_helper2: Box<dyn HelperDef + Send + Sync> = Box::new(helpers::StdinHelper {
-            inp: Mutex::new(BufReader::new(std::io::stdin()))
+            stdin
         });
         let ask_helper: Box<dyn HelperDef + Send + Sync> = Box::new(helpers::AskHelper {
             promptname: dotprompt.name.clone(),
-            inp:Mutex::new(BufReader::new(std::io::stdin()))
+            terminal: helpers::open_terminal(self.no_input)
         });
+        let role_helper: Box<dyn HelperDef + Send + Sync> = Box::new(helpers::RoleHelper);
+        let media_helper: Box<dyn HelperDef + Send + Sync> = Box::new(helpers::MediaHelper);
 
         let helpers_map:HashMap<&str, Box<dyn HelperDef + Send + Sync>> = HashMap::from([
             ("exec", exec_helper),
@@ -86,9 +111,12 @@ impl RenderCmd {
             ("stdin", stdin_helper),
             ("STDIN", stdin_helper2),
             ("ask", ask_helper),
+            ("role", role_helper),
+            ("media", media_helper),
         ]);
 
-        let mut rendered_dotprompt: String = dotprompt.render(inputs, helpers_map)?;
+        let rendered_dotprompt: String = dotprompt.render(inputs, helpers_map)?;
+        let mut rendered_dotprompt = PromptMessages::from(rendered_dotprompt.as_str()).to_string();
 
         if self.edit {
             rendered_dotprompt = editor.edit(&rendered_dotprompt, TextEditorFileType::Dotprompt)?;
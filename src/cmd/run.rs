@@ -1,13 +1,15 @@
 use clap::{Parser};
 use clap::{Arg, Command};
+use std::ffi::OsString;
 use std::io::{self, Write};
 use std::sync::Arc;
 use std::convert::TryFrom;
 use anyhow::{Context, Result};
 use thiserror::Error;
 use crate::dotprompt::renderers::argmatches::DotPromptArgMatches;
+use crate::dotprompt::renderers::interactive;
 use crate::executor::{ExecutionOutput, Executor, PromptInputs};
-use crate::dotprompt::{ DotPrompt};
+use crate::dotprompt::{ helpers, DotPrompt};
 
 #[derive(Parser)]
 pub struct RunCmd {
@@ -20,6 +22,12 @@ pub struct RunCmd {
     #[arg(long, short, help="Render only" )]
     pub render: bool,
 
+    #[arg(long, help="Ask for missing required inputs" )]
+    pub interactive: bool,
+
+    #[arg(long, help="Never ask for input, answer questions with their defaults" )]
+    pub no_input: bool,
+
     #[arg(trailing_var_arg = true)]
     pub prompt_args: Vec<String>,
 }
@@ -35,6 +43,41 @@ pub fn generate_arguments_from_dotprompt(mut command: Command, dotprompt: &DotPr
     Ok(command)
 }
 
+/// Describes the command with the prompt's metadata: the description as its
+/// about, and examples, version, author and tags after the help.
+pub fn describe_command(mut command: Command, dotprompt: &DotPrompt) -> Command {
+    let metadata = &dotprompt.frontmatter.metadata;
+
+    if let Some(description) = metadata.description.as_ref() {
+        command = command.about(description.clone());
+    }
+
+    let mut sections: Vec<String> = Vec::new();
+
+    if !metadata.examples.is_empty() {
+        let examples = metadata.examples.iter()
+            .map(|example| format!("  {example}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        sections.push(format!("Examples:\n{examples}"));
+    }
+
+    let tags = (!metadata.tags.is_empty()).then(|| metadata.tags.join(", "));
+    let details = [("Version", metadata.version.as_ref()), ("Author", metadata.author.as_ref()), ("Tags", tags.as_ref())]
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|value| format!("{name}: {value}")))
+        .collect::<Vec<_>>();
+    if !details.is_empty() {
+        sections.push(details.join("\n"));
+    }
+
+    if !sections.is_empty() {
+        command = command.after_help(sections.join("\n\n"));
+    }
+
+    command
+}
+
 #[derive(Error, Debug)]
 pub enum RunCmdError {
     #[error("'{0}' is required but not configured")]
@@ -50,14 +93,22 @@ impl RunCmd {
 
         let mut command: Command = Command::new(self.promptname.to_string());
 
+        command = describe_command(command, &dotprompt);
         command = generate_arguments_from_dotprompt(command, &dotprompt)?;
 
-        let params = [vec!["--".to_string()], self.prompt_args.clone()].concat();
+        let mut params = [vec!["--".to_string()], self.prompt_args.clone()].concat()
+            .into_iter().map(OsString::from).collect::<Vec<_>>();
+        if let Some(relaxed) = interactive::relaxed_matches(&command, &params)
+            && (dotprompt.frontmatter.interactive || self.interactive)
+            && let Some(terminal) = helpers::open_terminal(self.no_input) {
+            params = interactive::ask_missing_inputs(&command, &dotprompt, &relaxed, params, terminal.as_ref())?;
+        }
         let matches = command.get_matches_from(params);
 
         let argmatches = DotPromptArgMatches {
             matches,
-            dotprompt: &dotprompt
+            dotprompt: &dotprompt,
+            config: &executor.appconfig.inputs
         };
 
         let inputs: PromptInputs = argmatches.try_into()?;
@@ -0,0 +1,348 @@
+use clap::{Parser};
+use handlebars::*;
+use indexmap::IndexMap;
+use std::collections::HashMap;
+use std::io::{Cursor, Write};
+use std::sync::{Arc};
+use anyhow::{anyhow, bail, Result};
+use crate::config::appconfig::AppConfig;
+use crate::dotprompt::renderers::argmatches::inputs_from_values;
+use crate::dotprompt::messages::PromptMessages;
+use crate::dotprompt::renderers::Render;
+use crate::dotprompt::testcases::{parse_test_cases, TestCase};
+use crate::executor::json_output;
+use crate::executor::{PromptInputs};
+use crate::dotprompt::{helpers, DotPrompt, OutputFormat};
+use crate::storage::PromptFilesStorage;
+
+#[derive(Parser)]
+pub struct TestCmd {
+    #[arg(help="Prompts to test, all prompts with test cases if none given")]
+    pub promptnames: Vec<String>,
+}
+
+/// Stands in for the exec, prompt and ask helpers, answering with the output
+/// given in the test case for the helper's parameters joined by spaces.
+struct MockHelper {
+    helper: &'static str,
+    outputs: HashMap<String, String>
+}
+
+impl HelperDef for MockHelper {
+    fn call<'reg: 'rc, 'rc>(
+            &self,
+            h: &Helper<'rc>,
+            _: &'reg Handlebars<'reg>,
+            _: &'rc Context,
+            _: &mut RenderContext<'reg, 'rc>,
+            out: &mut dyn Output,
+        ) -> HelperResult {
+        let key = h.params().iter()
+            .map(|param| param.render())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let output = self.outputs.get(&key).ok_or(RenderError::from(RenderErrorReason::Other(
+            format!("{} '{key}' is not mocked in the test case", self.helper))))?;
+        out.write(output)?;
+
+        Ok(())
+    }
+}
+
+/// Runs a test case, returning the failed assertions.
+fn run_case(dotprompt: &DotPrompt, case: &TestCase, appconfig: &AppConfig) -> Result<Vec<String>> {
+    let inputs = case.inputs.iter()
+        .map(|(key, value)| Ok((key.clone(), serde_json::to_value(value)?)))
+        .collect::<Result<IndexMap<_, _>>>()?;
+    let mut inputs: PromptInputs = inputs_from_values(dotprompt, &inputs, &appconfig.inputs)?;
+
+    let stdin = Arc::new(helpers::SharedStdin::new(
+        Cursor::new(case.stdin.clone().unwrap_or_default()), appconfig.inputs.max_stdin_size));
+    stdin.insert_has_stdin(dotprompt, &mut inputs);
+
+    let exec_helper: Box<dyn HelperDef + Send + Sync> = Box::new(MockHelper {
+        helper: "exec",
+        outputs: case.exec.clone()
+    });
+    let prompt_helper: Box<dyn HelperDef + Send + Sync> = Box::new(MockHelper {
+        helper: "prompt",
+        outputs: case.prompt.clone()
+    });
+    let ask_helper: Box<dyn HelperDef + Send + Sync> = Box::new(MockHelper {
+        helper: "ask",
+        outputs: case.ask.clone()
+    });
+    let concat_helper: Box<dyn HelperDef + Send + Sync> = Box::new(helpers::ConcatHelper);
+    let stdin_helper: Box<dyn HelperDef + Send + Sync> = Box::new(helpers::StdinHelper {
+        stdin: stdin.clone()
+    });
+    let stdin_helper2: Box<dyn HelperDef + Send + Sync> = Box::new(helpers::StdinHelper {
+        stdin
+    });
+    let role_helper: Box<dyn HelperDef + Send + Sync> = Box::new(helpers::RoleHelper);
+    let media_helper: Box<dyn HelperDef + Send + Sync> = Box::new(helpers::MediaHelper);
+
+    let helpers_map:HashMap<&str, Box<dyn HelperDef + Send + Sync>> = HashMap::from([
+        ("exec", exec_helper),
+        ("prompt", prompt_helper),
+        ("concat", concat_helper),
+        ("stdin", stdin_helper),
+        ("STDIN", stdin_helper2),
+        ("ask", ask_helper),
+        ("role", role_helper),
+        ("media", media_helper),
+    ]);
+
+    let rendered = dotprompt.render(inputs, helpers_map)?;
+    let rendered = PromptMessages::from(rendered.as_str()).to_string();
+
+    let mut failures = case.expect.check("rendered prompt", &rendered);
+
+    match (case.response.as_ref(), case.expect_response.as_ref()) {
+        (Some(response), expect_response) => {
+            let response = if dotprompt.frontmatter.output.format == OutputFormat::Json {
+                let output_schema = (!dotprompt.frontmatter.output.schema.is_empty())
+                    .then(|| dotprompt.output_json_schema());
+                match json_output::parse_and_validate(response, output_schema.as_ref()) {
+                    Ok(response) => Some(response),
+                    Err(reason) => {
+                        failures.push(format!("response is invalid: {reason}"));
+                        None
+                    }
+                }
+            } else {
+                Some(response.clone())
+            };
+
+            if let (Some(response), Some(expect_response)) = (response, expect_response) {
+                failures.extend(expect_response.check("response", &response));
+            }
+        }
+        (None, Some(_)) => failures.push("expect_response given without a response".to_string()),
+        (None, None) => {}
+    }
+
+    Ok(failures)
+}
+
+impl TestCmd {
+    pub fn exec(&self,
+        storage: &impl PromptFilesStorage,
+        out: &mut impl Write,
+        appconfig: &AppConfig
+    )-> Result<()> {
+
+        let mut promptnames = if self.promptnames.is_empty() {
+            storage.list()?.into_keys().collect::<Vec<_>>()
+        } else {
+            self.promptnames.clone()
+        };
+        promptnames.sort();
+
+        let partials = storage.partials()?;
+        let (mut passed, mut failed) = (0, 0);
+
+        for promptname in promptnames {
+            if storage.exists(&promptname).is_none() {
+                bail!("Could not find a prompt with the name \"{promptname}\"");
+            }
+
+            let (_, promptdata) = storage.load(&promptname)?;
+            let testsdata = storage.load_tests(&promptname)?;
+            let cases = parse_test_cases(&promptdata, testsdata.as_deref())
+                .map_err(|err| anyhow!("Could not read test cases of {promptname}: {err}"))?;
+
+            if cases.is_empty() {
+                if !self.promptnames.is_empty() {
+                    writeln!(out, "{promptname}: no test cases")?;
+                }
+                continue;
+            }
+
+            let dotprompt = DotPrompt::try_from_extended(&promptname, &promptdata,
+                |parent| anyhow::Ok(storage.load(parent)?.1))?
+                .with_partials(partials.clone());
+
+            for (idx, case) in cases.iter().enumerate() {
+                let casename = case.name.clone().unwrap_or_else(|| format!("case {}", idx + 1));
+                let failures = match run_case(&dotprompt, case, appconfig) {
+                    Ok(failures) => failures,
+                    Err(err) => vec![err.to_string()]
+                };
+
+                if failures.is_empty() {
+                    passed += 1;
+                    writeln!(out, "{promptname}: {casename} ... ok")?;
+                } else {
+                    failed += 1;
+                    writeln!(out, "{promptname}: {casename} ... FAILED")?;
+                    for failure in failures {
+                        writeln!(out, "  - {}", failure.replace('\n', "\n    "))?;
+                    }
+                }
+            }
+        }
+
+        writeln!(out, "\n{passed} passed, {failed} failed")?;
+
+        if failed > 0 {
+            bail!("{failed} test case(s) failed");
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cmd::test::TestCmd;
+    use crate::config::appconfig::AppConfig;
+    use crate::storage::promptfiles_mem::InMemoryPromptFilesStorage;
+    use crate::storage::PromptFilesStorage;
+
+    const SUMMARIZE: &str = r#"---
+input:
+  schema:
+    words?: integer = 50, Summary length
+    lang?: string
+    text!: string
+tests:
+  - name: defaults
+    inputs:
+      text: The article
+    expect:
+      snapshot: |
+        Summarize in 50 words: The article
+  - name: language
+    inputs:
+      words: 10
+      lang: French
+      text: The article
+    expect:
+      contains: [in French]
+      matches: ["10 words"]
+---
+Summarize in {{words}} words{{#if lang}} in {{lang}}{{/if}}: {{text}}"#;
+
+    fn run(storage: &InMemoryPromptFilesStorage, promptnames: &[&str]) -> (bool, String) {
+        let cmd = TestCmd {
+            promptnames: promptnames.iter().map(|name| name.to_string()).collect()
+        };
+        let mut buf = Vec::new();
+        let result = cmd.exec(storage, &mut buf, &AppConfig::default());
+        (result.is_ok(), String::from_utf8(buf).unwrap())
+    }
+
+    #[test]
+    fn test_passing_cases() {
+        let storage = InMemoryPromptFilesStorage::default();
+        storage.store("summarize", SUMMARIZE).unwrap();
+        storage.store("plain", "No tests here").unwrap();
+
+        let (ok, output) = run(&storage, &[]);
+        assert!(ok);
+        assert_eq!(output, "summarize: defaults ... ok\nsummarize: language ... ok\n\n2 passed, 0 failed\n");
+    }
+
+    #[test]
+    fn test_failing_cases() {
+        let storage = InMemoryPromptFilesStorage::default();
+        storage.store("summarize", SUMMARIZE).unwrap();
+        storage.store_tests("summarize", r#"
+- name: wrong words
+  inputs:
+    text: The article
+  expect:
+    contains: [10 words]
+- name: unknown input
+  inputs:
+    txt: The article
+"#);
+
+        let (ok, output) = run(&storage, &["summarize"]);
+        assert!(!ok);
+        assert!(output.contains("summarize: wrong words ... FAILED\n  - rendered prompt does not contain \"10 words\"\n"));
+        assert!(output.contains("summarize: unknown input ... FAILED\n  - unknown input 'txt'\n"));
+        assert!(output.ends_with("2 passed, 2 failed\n"));
+    }
+
+    #[test]
+    fn test_mocked_helpers() {
+        let storage = InMemoryPromptFilesStorage::default();
+        storage.store("commit", r#"---
+tests:
+  - stdin: fix typo
+    exec:
+      git diff --staged: "-teh\n+the"
+    prompt:
+      style: Use the imperative mood
+    expect:
+      snapshot: |
+        Use the imperative mood
+        -teh
+        +the
+        Notes: fix typo
+  - name: unmocked
+    expect:
+      contains: [diff]
+---
+{{prompt "style"}}
+{{exec "git" "diff" "--staged"}}
+Notes: {{stdin}}"#).unwrap();
+
+        let (ok, output) = run(&storage, &["commit"]);
+        assert!(!ok);
+        assert!(output.contains("commit: case 1 ... ok\n"));
+        assert!(output.contains("commit: unmocked ... FAILED\n"));
+        assert!(output.contains("not mocked in the test case"));
+    }
+
+    #[test]
+    fn test_shared_stdin() {
+        let storage = InMemoryPromptFilesStorage::default();
+        storage.store("echo", r#"---
+tests:
+  - stdin: hello
+    expect:
+      snapshot: hello hello
+  - expect:
+      snapshot: Nothing piped
+---
+{{#if has_stdin}}{{STDIN}} {{stdin}}{{else}}Nothing piped{{/if}}"#).unwrap();
+
+        let (ok, output) = run(&storage, &["echo"]);
+        assert!(ok, "{output}");
+    }
+
+    #[test]
+    fn test_stubbed_response() {
+        let storage = InMemoryPromptFilesStorage::default();
+        storage.store("rate", r#"---
+output:
+  format: json
+  schema:
+    score: integer(min=1, max=5)
+tests:
+  - name: valid
+    response: 'Here you go: {"score": 4,}'
+    expect_response:
+      snapshot: '{"score": 4}'
+  - name: out of range
+    response: '{"score": 7}'
+---
+Rate this"#).unwrap();
+
+        let (ok, output) = run(&storage, &["rate"]);
+        assert!(!ok);
+        assert!(output.contains("rate: valid ... ok\n"));
+        assert!(output.contains("rate: out of range ... FAILED\n  - response is invalid: $.score: 7 is greater than 5\n"));
+    }
+
+    #[test]
+    fn test_prompt_not_found() {
+        let storage = InMemoryPromptFilesStorage::default();
+        let (ok, _) = run(&storage, &["missing"]);
+        assert!(!ok);
+    }
+}
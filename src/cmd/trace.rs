@@ -0,0 +1,193 @@
+use std::io::Write;
+
+use anyhow::{anyhow, bail, Result};
+use clap::Parser;
+
+use crate::stats::store::{StatsStore, TraceNode};
+
+// Runs shown by `--list`
+const LISTED_RUNS: u32 = 20;
+
+#[derive(Parser)]
+pub struct TraceCmd {
+    #[arg(help="Id of the run to trace, the last run if not given")]
+    pub run_id: Option<String>,
+
+    #[arg(short, long, conflicts_with="run_id", help="List the recent runs with their ids")]
+    pub list: bool,
+}
+
+// Prompt, model, tokens, latency and outcome of an execution
+fn describe(node: &TraceNode) -> String {
+    let execution = &node.execution;
+    let mut parts = vec![match execution.position {
+        Some(position) => format!("#{position} {}", execution.promptname),
+        None => execution.promptname.clone()
+    }];
+
+    if let Some(call) = node.calls.last() {
+        parts.push(format!("{}/{}", call.provider, call.model));
+    }
+
+    let requests = node.calls.iter().filter(|call| !call.cached).collect::<Vec<_>>();
+    if !requests.is_empty() {
+        let prompt_tokens: u32 = requests.iter().map(|call| call.prompt_tokens).sum();
+        let completion_tokens: u32 = requests.iter().map(|call| call.completion_tokens).sum();
+        let time_taken: u32 = requests.iter().map(|call| call.time_taken).sum();
+        parts.push(format!("{prompt_tokens}+{completion_tokens} tokens"));
+        parts.push(format!("{time_taken}s"));
+    }
+    if requests.len() > 1 {
+        parts.push(format!("{} calls", requests.len()));
+    }
+
+    let status = match (execution.error.as_ref(), node.calls.last()) {
+        (Some(error), _) => format!("failed: {}", error.lines().next().unwrap_or_default()),
+        (None, Some(call)) if call.cached => "cached".to_string(),
+        (None, Some(call)) if call.success => "ok".to_string(),
+        (None, Some(_)) => "failed".to_string(),
+        (None, None) => "no LLM call".to_string()
+    };
+    parts.push(status);
+
+    parts.join("  ")
+}
+
+fn print_children(out: &mut impl Write, nodes: &[TraceNode], parent: &TraceNode, prefix: &str) -> Result<()> {
+    let mut children = nodes.iter()
+        .filter(|node| node.execution.parent == Some(parent.execution.node))
+        .collect::<Vec<_>>();
+    children.sort_by_key(|node| (node.execution.position, node.execution.node));
+
+    for (idx, child) in children.iter().enumerate() {
+        let last = idx + 1 == children.len();
+        let (branch, indent) = if last { ("└── ", "    ") } else { ("├── ", "│   ") };
+        writeln!(out, "{prefix}{branch}{}", describe(child))?;
+        print_children(out, nodes, child, &format!("{prefix}{indent}"))?;
+    }
+
+    Ok(())
+}
+
+fn list_runs(store: &impl StatsStore, out: &mut impl Write) -> Result<()> {
+    for run in store.runs(LISTED_RUNS)? {
+        let mut line = format!("{}  {}  {}", run.run_id, run.created.format("%Y-%m-%d %H:%M:%S"), run.promptname);
+        if let Some(error) = run.error.as_ref() {
+            line.push_str(&format!("  failed: {}", error.lines().next().unwrap_or_default()));
+        }
+        writeln!(out, "{line}")?;
+    }
+
+    Ok(())
+}
+
+impl TraceCmd {
+    pub fn exec(&self, store: &impl StatsStore, out: &mut impl Write) -> Result<()> {
+        if self.list {
+            return list_runs(store, out);
+        }
+
+        let run_id = match self.run_id.as_ref() {
+            Some(run_id) => run_id.clone(),
+            None => store.last_run_id()?.ok_or(anyhow!("No runs recorded yet"))?
+        };
+
+        let nodes = store.trace(&run_id)?;
+        let Some(first) = nodes.first() else {
+            bail!("Could not find a run with the id \"{run_id}\"");
+        };
+
+        writeln!(out, "Run {run_id} ({})", first.execution.created.format("%Y-%m-%d %H:%M:%S"))?;
+        for root in nodes.iter().filter(|node| node.execution.parent.is_none()) {
+            writeln!(out, "{}", describe(root))?;
+            print_children(out, &nodes, root, "")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::stats::rusqlite_store::RusqliteStore;
+    use crate::stats::store::{ExecutionRecord, LogRecord};
+
+    fn execution(node: u32, parent: Option<u32>, position: Option<u32>, promptname: &str) -> ExecutionRecord {
+        ExecutionRecord {
+            run_id: "run1".to_string(),
+            node,
+            parent,
+            depth: parent.map_or(0, |parent| parent + 1),
+            position,
+            promptname: promptname.to_string(),
+            error: None,
+            created: Utc::now()
+        }
+    }
+
+    fn call(node: u32, tokens: (u32, u32), success: bool, cached: bool) -> LogRecord {
+        LogRecord {
+            promptname: String::new(),
+            provider: "ollama".to_string(),
+            model: "llama3".to_string(),
+            variant: None,
+            group: None,
+            prompt_tokens: tokens.0,
+            completion_tokens: tokens.1,
+            result: String::new(),
+            success,
+            time_taken: 2,
+            created: Utc::now(),
+            cache_key: None,
+            run_id: Some("run1".to_string()),
+            node: Some(node),
+            cached
+        }
+    }
+
+    #[test]
+    fn test_trace() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = RusqliteStore::new(dir.path().to_path_buf()).unwrap();
+
+        store.log_execution(execution(0, None, None, "aggregator")).unwrap();
+        store.log_execution(execution(1, Some(0), Some(1), "inspect")).unwrap();
+        store.log_execution(execution(3, Some(0), Some(2), "analyze")).unwrap();
+        store.log_execution(execution(2, Some(1), Some(1), "summarize")).unwrap();
+        store.log_execution_error("run1", 3, "Invalid JSON output after 3 attempt(s)").unwrap();
+
+        store.log(call(2, (10, 5), false, false)).unwrap();
+        store.log(call(2, (12, 6), true, false)).unwrap();
+        store.log(call(1, (0, 0), true, true)).unwrap();
+        store.log(call(0, (100, 50), true, false)).unwrap();
+
+        let mut out = Vec::new();
+        TraceCmd { run_id: None, list: false }.exec(&store, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.starts_with("Run run1 ("));
+        assert_eq!(out.lines().skip(1).collect::<Vec<_>>(), vec![
+            "aggregator  ollama/llama3  100+50 tokens  2s  ok",
+            "├── #1 inspect  ollama/llama3  cached",
+            "│   └── #1 summarize  ollama/llama3  22+11 tokens  4s  2 calls  ok",
+            "└── #2 analyze  failed: Invalid JSON output after 3 attempt(s)",
+        ]);
+
+        let summary = store.summary(None, None, None, None, None).unwrap();
+        assert_eq!(summary[0].count, 3);
+
+        let result = TraceCmd { run_id: Some("run2".to_string()), list: false }.exec(&store, &mut Vec::new());
+        assert_eq!(result.unwrap_err().to_string(), "Could not find a run with the id \"run2\"");
+
+        let mut out = Vec::new();
+        TraceCmd { run_id: None, list: true }.exec(&store, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("run1  ") && out.trim_end().ends_with("  aggregator"), "{out}");
+        assert_eq!(out.lines().count(), 1);
+
+        assert_eq!(store.records(None).unwrap().len(), 3);
+    }
+}
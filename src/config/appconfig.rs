@@ -17,6 +17,10 @@ pub struct AppConfig {
     #[serde(default)]
     pub import: Import,
     #[serde(default)]
+    pub inputs: Inputs,
+    #[serde(default)]
+    pub nested: Nested,
+    #[serde(default)]
     pub providers: Providers,
     #[serde(default)]
     pub groups: HashMap<String, GroupConfig>,
@@ -38,6 +42,81 @@ pub struct Import {
     pub force: bool,
 }
 
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 1024 * 1024;
+pub const DEFAULT_MAX_MEDIA_SIZE: u64 = 20 * 1024 * 1024;
+pub const DEFAULT_MAX_STDIN_SIZE: u64 = 10 * 1024 * 1024;
+
+fn default_max_file_size() -> u64 {
+    DEFAULT_MAX_FILE_SIZE
+}
+
+fn default_max_media_size() -> u64 {
+    DEFAULT_MAX_MEDIA_SIZE
+}
+
+fn default_max_stdin_size() -> u64 {
+    DEFAULT_MAX_STDIN_SIZE
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Inputs {
+    #[serde(default = "default_max_file_size")]
+    pub max_file_size: u64,
+    #[serde(default = "default_max_media_size")]
+    pub max_media_size: u64,
+    #[serde(default = "default_max_stdin_size")]
+    pub max_stdin_size: u64,
+}
+
+impl Default for Inputs {
+    fn default() -> Self {
+        Inputs {
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            max_media_size: DEFAULT_MAX_MEDIA_SIZE,
+            max_stdin_size: DEFAULT_MAX_STDIN_SIZE
+        }
+    }
+}
+
+pub const DEFAULT_MAX_PARALLEL: usize = 4;
+pub const DEFAULT_MAX_DEPTH: usize = 8;
+pub const DEFAULT_MAX_LLM_CALLS: u32 = 50;
+
+fn default_max_parallel() -> usize {
+    DEFAULT_MAX_PARALLEL
+}
+
+fn default_max_depth() -> usize {
+    DEFAULT_MAX_DEPTH
+}
+
+fn default_max_llm_calls() -> u32 {
+    DEFAULT_MAX_LLM_CALLS
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Nested {
+    // Nested prompt and exec calls run at the same time, 1 runs them in order
+    #[serde(default = "default_max_parallel")]
+    pub max_parallel: usize,
+    // Levels of prompts nested in the prompt run
+    #[serde(default = "default_max_depth")]
+    pub max_depth: usize,
+    // Requests to providers per run, nested prompts included
+    #[serde(default = "default_max_llm_calls")]
+    pub max_llm_calls: u32,
+}
+
+impl Default for Nested {
+    fn default() -> Self {
+        Nested {
+            max_parallel: DEFAULT_MAX_PARALLEL,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_llm_calls: DEFAULT_MAX_LLM_CALLS
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Default, Clone)]
 pub struct GlobalProviderProperties {
     pub temperature: Option<f32>,
@@ -54,9 +133,9 @@ impl From<&ParsedFrontmatter> for GlobalProviderProperties {
             temperature: fm.config.as_ref().and_then(|config| config.temperature),
             max_tokens: fm.config.as_ref().and_then(|config| config.max_output_tokens),
             model:  fm.model.clone(),
-            system: None,
+            system: fm.config.as_ref().and_then(|config| config.system.clone()),
             cache_ttl: fm.config.as_ref().and_then(|config| config.cache_ttl),
-            stream: None
+            stream: fm.config.as_ref().and_then(|config| config.stream),
         }
     }
 }
@@ -204,6 +283,55 @@ mod tests {
         assert!(config.is_ok(), "Should parse config with empty providers section");
     }
 
+    #[test]
+    fn test_global_properties_from_frontmatter() {
+        let dotprompt = crate::dotprompt::DotPrompt::try_from(r#"---
+model: anthropic/claude-sonnet-4-5
+config:
+  temperature: 0.2
+  max_tokens: 300
+  cache_ttl: 60
+  system: You are a {{persona}}
+  stream: true
+---
+Template"#).unwrap();
+
+        let props = GlobalProviderProperties::from(&dotprompt.frontmatter);
+        assert_eq!(props.model, Some("anthropic/claude-sonnet-4-5".to_string()));
+        assert_eq!(props.temperature, Some(0.2));
+        assert_eq!(props.max_tokens, Some(300));
+        assert_eq!(props.cache_ttl, Some(60));
+        assert_eq!(props.system, Some("You are a {{persona}}".to_string()));
+        assert_eq!(props.stream, Some(true));
+    }
+
+    #[test]
+    fn test_inputs_section() {
+        let config = AppConfig::try_from("").unwrap();
+        assert_eq!(config.inputs.max_file_size, DEFAULT_MAX_FILE_SIZE);
+        assert_eq!(config.inputs.max_media_size, DEFAULT_MAX_MEDIA_SIZE);
+
+        let config = AppConfig::try_from(r#"
+            [inputs]
+            max_file_size = 2048
+        "#).unwrap();
+        assert_eq!(config.inputs.max_file_size, 2048);
+        assert_eq!(config.inputs.max_media_size, DEFAULT_MAX_MEDIA_SIZE);
+    }
+
+    #[test]
+    fn test_nested_section() {
+        let config = AppConfig::try_from("").unwrap();
+        assert_eq!(config.nested.max_parallel, DEFAULT_MAX_PARALLEL);
+
+        assert_eq!(config.nested.max_depth, DEFAULT_MAX_DEPTH);
+
+        let config = AppConfig::try_from("[nested]\nmax_parallel = 1\nmax_llm_calls = 10").unwrap();
+        assert_eq!(config.nested.max_parallel, 1);
+        assert_eq!(config.nested.max_llm_calls, 10);
+        assert_eq!(config.nested.max_depth, DEFAULT_MAX_DEPTH);
+    }
+
     #[test]
     fn test_groups() {
         let toml_content = r#"
@@ -0,0 +1,222 @@
+use regex::Regex;
+
+use crate::dotprompt::ParseError;
+
+/// Constraints on the values of a schema field, given in parentheses after
+/// its type, e.g. `integer(min=1, max=500)` or `string(pattern="^[a-z]+$")`.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Constraints {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+    pub pattern: Option<String>,
+}
+
+/// Splits `text` at the top level occurrences of `separator`, i.e. outside
+/// of double quotes and parentheses.
+fn split_top_level(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut in_quotes = false;
+    let mut start = 0;
+
+    for (idx, c) in text.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '(' if !in_quotes => depth += 1,
+            ')' if !in_quotes => depth -= 1,
+            c if c == separator && !in_quotes && depth == 0 => {
+                parts.push(&text[start..idx]);
+                start = idx + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+/// Takes the constraints out of a schema value, returning the value without
+/// them, and the constraints if any.
+pub fn take_constraints(key: &str, value: &str) -> Result<(String, Option<String>), ParseError> {
+    let Some(open) = value.find('(') else {
+        return Ok((value.to_string(), None))
+    };
+
+    // Parentheses in defaults or descriptions are not constraints
+    if value[..open].contains([',', '=']) {
+        return Ok((value.to_string(), None))
+    }
+
+    let mut depth = 0;
+    let mut in_quotes = false;
+    for (idx, c) in value[open..].char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '(' if !in_quotes => depth += 1,
+            ')' if !in_quotes => {
+                depth -= 1;
+                if depth == 0 {
+                    let close = open + idx;
+                    return Ok((
+                        format!("{}{}", &value[..open], &value[close + 1..]),
+                        Some(value[open + 1..close].to_string())
+                    ))
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Err(ParseError::ParseSchemaError {
+        key: key.to_string(),
+        reason: format!("Unclosed constraints in '{value}'")
+    })
+}
+
+impl Constraints {
+    pub fn parse(key: &str, data_type: &str, constraints: &str) -> Result<Self, ParseError> {
+        let error = |reason: String| ParseError::ParseSchemaError {
+            key: key.to_string(),
+            reason: format!("Invalid constraints for '{key}': {reason}")
+        };
+
+        let numeric = data_type == "integer" || data_type == "number";
+        let textual = data_type == "string";
+
+        let mut parsed = Constraints::default();
+
+        for constraint in split_top_level(constraints, ',') {
+            if constraint.trim().is_empty() {
+                continue;
+            }
+
+            let (name, value) = constraint.split_once('=')
+                .ok_or(error(format!("expected name=value, got '{}'", constraint.trim())))?;
+            let name = name.trim();
+            let value = value.trim();
+            let value = value.strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .unwrap_or(value);
+
+            match name {
+                "min" | "max" if numeric => {
+                    let number = value.parse::<f64>()
+                        .map_err(|_| error(format!("{name} must be a number")))?;
+                    if name == "min" {
+                        parsed.min = Some(number);
+                    } else {
+                        parsed.max = Some(number);
+                    }
+                }
+                "min_length" | "max_length" if textual => {
+                    let length = value.parse::<usize>()
+                        .map_err(|_| error(format!("{name} must be a positive integer")))?;
+                    if name == "min_length" {
+                        parsed.min_length = Some(length);
+                    } else {
+                        parsed.max_length = Some(length);
+                    }
+                }
+                "pattern" if textual => {
+                    Regex::new(value).map_err(|err| error(err.to_string()))?;
+                    parsed.pattern = Some(value.to_string());
+                }
+                "min" | "max" | "min_length" | "max_length" | "pattern" => {
+                    return Err(error(format!("{name} is not applicable to {data_type}")))
+                }
+                _ => return Err(error(format!("unknown constraint {name}")))
+            }
+        }
+
+        if let (Some(min), Some(max)) = (parsed.min, parsed.max) && min > max {
+            return Err(error("min is greater than max".to_string()))
+        }
+        if let (Some(min), Some(max)) = (parsed.min_length, parsed.max_length) && min > max {
+            return Err(error("min_length is greater than max_length".to_string()))
+        }
+
+        Ok(parsed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Constraints::default()
+    }
+
+    pub fn check_number(&self, value: f64) -> Result<(), String> {
+        if let Some(min) = self.min && value < min {
+            return Err(format!("must be at least {min}"))
+        }
+        if let Some(max) = self.max && value > max {
+            return Err(format!("must be at most {max}"))
+        }
+        Ok(())
+    }
+
+    pub fn check_string(&self, value: &str) -> Result<(), String> {
+        let length = value.chars().count();
+        if let Some(min_length) = self.min_length && length < min_length {
+            return Err(format!("must be at least {min_length} characters long"))
+        }
+        if let Some(max_length) = self.max_length && length > max_length {
+            return Err(format!("must be at most {max_length} characters long"))
+        }
+        if let Some(pattern) = self.pattern.as_ref()
+            && let Ok(regex) = Regex::new(pattern)
+            && !regex.is_match(value) {
+            return Err(format!("must match the pattern {pattern}"))
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_constraints() {
+        assert_eq!(take_constraints("words", "integer(min=1, max=500) = 50, Summary length").unwrap(),
+            ("integer = 50, Summary length".to_string(), Some("min=1, max=500".to_string())));
+        assert_eq!(take_constraints("tags", r#"string(pattern="^(a|b),$")[], Tags"#).unwrap(),
+            ("string[], Tags".to_string(), Some(r#"pattern="^(a|b),$""#.to_string())));
+        assert_eq!(take_constraints("name", "string, Name (optional)").unwrap(),
+            ("string, Name (optional)".to_string(), None));
+        assert!(take_constraints("words", "integer(min=1").is_err());
+    }
+
+    #[test]
+    fn test_parse() {
+        let constraints = Constraints::parse("words", "integer", "min=1, max=500").unwrap();
+        assert_eq!(constraints, Constraints { min: Some(1.0), max: Some(500.0), ..Default::default() });
+
+        let constraints = Constraints::parse("slug", "string", r#"pattern="^[a-z-]+$", max_length=20"#).unwrap();
+        assert_eq!(constraints.pattern.as_deref(), Some("^[a-z-]+$"));
+        assert_eq!(constraints.max_length, Some(20));
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(Constraints::parse("words", "integer", "max_length=3").is_err());
+        assert!(Constraints::parse("name", "string", "min=1").is_err());
+        assert!(Constraints::parse("words", "integer", "min=ten").is_err());
+        assert!(Constraints::parse("words", "integer", "min=10, max=1").is_err());
+        assert!(Constraints::parse("name", "string", "pattern=[").is_err());
+        assert!(Constraints::parse("name", "string", "unique=true").is_err());
+    }
+
+    #[test]
+    fn test_checks() {
+        let constraints = Constraints::parse("words", "number", "min=0.5, max=2").unwrap();
+        assert!(constraints.check_number(1.0).is_ok());
+        assert_eq!(constraints.check_number(-5.0), Err("must be at least 0.5".to_string()));
+        assert_eq!(constraints.check_number(3.0), Err("must be at most 2".to_string()));
+
+        let constraints = Constraints::parse("slug", "string", "pattern=^[a-z]+$, min_length=2, max_length=4").unwrap();
+        assert!(constraints.check_string("abc").is_ok());
+        assert!(constraints.check_string("a").is_err());
+        assert!(constraints.check_string("abcde").is_err());
+        assert_eq!(constraints.check_string("AB"), Err("must match the pattern ^[a-z]+$".to_string()));
+    }
+}
@@ -0,0 +1,208 @@
+use std::fmt;
+
+use regex::Regex;
+
+use crate::dotprompt::{opening_delimiter_line, scan_sections, Frontmatter, ParseError};
+
+/// A parse error located in the prompt file it comes from, rendered with
+/// the offending source line.
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub error: ParseError,
+    pub path: Option<String>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub key: Option<String>,
+    // Source line the error points at
+    pub snippet: Option<String>,
+}
+
+/// Position of the line declaring `key` in the frontmatter, which starts at
+/// line `first_line` of the prompt file.
+fn locate_key(frontmatter: &str, first_line: usize, key: &str) -> Option<(usize, usize)> {
+    frontmatter.lines().enumerate().find_map(|(idx, line)| {
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+        let (unquoted, quoted) = match trimmed.strip_prefix(['"', '\'']) {
+            Some(unquoted) => (unquoted, 1),
+            None => (trimmed, 0)
+        };
+
+        let declares = unquoted.strip_prefix(key)
+            .is_some_and(|rest| rest.starts_with(['?', '!', ':', '(', '"', '\'', ' ']));
+        declares.then_some((first_line + idx, indent + quoted + 1))
+    })
+}
+
+impl Diagnostic {
+    pub fn new(error: ParseError, path: Option<&str>, promptdata: &str) -> Self {
+        let sections = scan_sections(promptdata).ok();
+        let frontmatter = sections.as_ref().and_then(|sections| sections.frontmatter.as_deref()
+            .map(|frontmatter| (frontmatter, sections.frontmatter_line)));
+
+        let position = match &error {
+            ParseError::FrontmatterNotWellFormed => opening_delimiter_line(promptdata).map(|line| (line, 1)),
+            ParseError::ParseFrontmatterError(err) => frontmatter.and_then(|(frontmatter, first_line)| {
+                // Errors of the conversion from an already parsed YAML value
+                // have no location, parsing the frontmatter again gives it.
+                let location = err.location().or_else(|| {
+                    serde_yaml::from_str::<Frontmatter>(frontmatter).err().and_then(|err| err.location())
+                })?;
+                Some((first_line + location.line() - 1, location.column()))
+            }),
+            error => error.key().zip(frontmatter)
+                .and_then(|(key, (frontmatter, first_line))| locate_key(frontmatter, first_line, key))
+        };
+
+        Diagnostic {
+            key: error.key().map(|key| key.to_string()),
+            error,
+            path: path.map(|path| path.to_string()),
+            line: position.map(|(line, _)| line),
+            column: position.map(|(_, column)| column),
+            snippet: position.and_then(|(line, _)| promptdata.lines().nth(line - 1)).map(|line| line.to_string()),
+        }
+    }
+
+    /// Diagnostic of an error of a prompt that may extend others, located in
+    /// the parent it comes from when `load` gives the parent's path and
+    /// content. Without them, the error is not located.
+    pub fn extended(error: ParseError, path: Option<&str>, promptdata: &str,
+        load: impl Fn(&str) -> Option<(String, String)>) -> Self {
+        match error {
+            ParseError::InParent { name, source } => match load(&name) {
+                Some((path, promptdata)) => Diagnostic::new(*source, Some(&path), &promptdata),
+                None => Diagnostic::new(ParseError::InParent { name, source }, path, promptdata)
+            },
+            error => Diagnostic::new(error, path, promptdata)
+        }
+    }
+
+    /// Message of the error, without the YAML parser's own location which is
+    /// relative to the frontmatter.
+    pub fn message(&self) -> String {
+        let message = self.error.to_string();
+        match (&self.error, self.line) {
+            (ParseError::ParseFrontmatterError(_), Some(_)) => {
+                let location = Regex::new(r" at line \d+ column \d+").unwrap();
+                location.replace_all(&message, "").into_owned()
+            }
+            _ => message
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = self.message();
+        match (self.path.as_ref(), self.line, self.column) {
+            (Some(path), Some(line), Some(column)) => write!(f, "{path}:{line}:{column}: {message}")?,
+            (Some(path), _, _) => write!(f, "{path}: {message}")?,
+            (None, Some(line), Some(column)) => write!(f, "line {line}, column {column}: {message}")?,
+            _ => write!(f, "{message}")?,
+        }
+
+        if let (Some(line), Some(column), Some(snippet)) = (self.line, self.column, self.snippet.as_ref()) {
+            let gutter = " ".repeat(line.to_string().len());
+            write!(f, "\n{gutter} |\n{line} | {snippet}\n{gutter} | {}^", " ".repeat(column - 1))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostic {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dotprompt::DotPrompt;
+
+    fn diagnostic(promptdata: &str) -> Diagnostic {
+        let err = DotPrompt::try_from(promptdata).unwrap_err();
+        Diagnostic::new(err, Some("summarize.prompt"), promptdata)
+    }
+
+    #[test]
+    fn test_schema_error() {
+        let diagnostic = diagnostic("# Summarizes text
+---
+input:
+  schema:
+    words?: integer
+    flag: boolean = true
+---
+Summarize");
+
+        assert_eq!((diagnostic.line, diagnostic.column), (Some(6), Some(5)));
+        assert_eq!(diagnostic.key.as_deref(), Some("flag"));
+        assert_eq!(diagnostic.to_string(), "summarize.prompt:6:5: Error parsing schema: Boolean field 'flag' cannot have a default value
+  |
+6 |     flag: boolean = true
+  |     ^");
+    }
+
+    #[test]
+    fn test_yaml_syntax_error() {
+        let diagnostic = diagnostic("---
+model: x
+input: [
+---
+Summarize");
+
+        assert_eq!(diagnostic.line, Some(4));
+        assert!(!diagnostic.message().contains("at line"));
+    }
+
+    #[test]
+    fn test_yaml_type_error() {
+        let diagnostic = diagnostic("---
+model: x
+tags: 5
+---
+Summarize");
+
+        assert_eq!((diagnostic.line, diagnostic.column), (Some(3), Some(7)));
+    }
+
+    #[test]
+    fn test_unclosed_frontmatter() {
+        let diagnostic = diagnostic("\n\n---\nmodel: x\nSummarize");
+
+        assert_eq!((diagnostic.line, diagnostic.column), (Some(3), Some(1)));
+        assert_eq!(diagnostic.snippet.as_deref(), Some("---"));
+    }
+
+    #[test]
+    fn test_error_in_parent() {
+        let parent = "---\nmodel: x\ninput: [\n---\nSummarize {{#block \"a\"}}{{/block}}";
+        let child = "---\nextends: base\n---\n{{#block \"a\"}}text{{/block}}";
+        let load = |name: &str| (name == "base").then(|| parent.to_string()).ok_or(ParseError::MissingTemplate);
+
+        let err = DotPrompt::try_from_extended("child", child, load).unwrap_err();
+        let diagnostic = Diagnostic::extended(err, Some("child.prompt"), child,
+            |name| (name == "base").then(|| ("base.prompt".to_string(), parent.to_string())));
+        assert_eq!(diagnostic.path.as_deref(), Some("base.prompt"));
+        assert_eq!(diagnostic.line, Some(4));
+        assert_eq!(diagnostic.snippet.as_deref(), Some("---"));
+
+        let err = DotPrompt::try_from_extended("child", child, load).unwrap_err();
+        let diagnostic = Diagnostic::extended(err, Some("child.prompt"), child, |_| None);
+        assert_eq!(diagnostic.line, None);
+        assert!(diagnostic.to_string().starts_with("child.prompt: In parent prompt 'base': Error parsing frontmatter"),
+            "{diagnostic}");
+    }
+
+    #[test]
+    fn test_unlocated_error() {
+        let err = DotPrompt::try_from("---\nmodel: x\n---\n").unwrap_err();
+        let diagnostic = Diagnostic::new(err, None, "---\nmodel: x\n---\n");
+
+        assert_eq!(diagnostic.line, None);
+        assert_eq!(diagnostic.to_string(), "A template is required but not found");
+    }
+}
@@ -1,36 +1,248 @@
-use std::{io::{BufRead, BufReader, Read}, sync::Mutex};
-use std::io::{Write};
+use std::sync::Arc;
 
 use handlebars::*;
+use serde_json::Value;
 
-pub struct AskHelper<R: Read> {
+use crate::dotprompt::helpers::Terminal;
+use crate::dotprompt::messages::strip_value_markers;
+
+/// Asks the user a question, e.g.
+/// `{{ask "Environment?" choices="dev,prod" default="dev"}}`. Supports
+/// `secret=true`, `multiline=true`, and `confirm=true` giving a boolean.
+pub struct AskHelper {
     pub promptname: String,
-    pub inp: Mutex<BufReader<R>>
+    // None when input is disabled or there is no terminal
+    pub terminal: Option<Arc<dyn Terminal>>,
 }
 
-impl<R:Read> HelperDef for AskHelper<R> {
-    fn call<'reg: 'rc, 'rc>(
+#[derive(Debug, Default, PartialEq)]
+struct Question {
+    text: String,
+    choices: Vec<String>,
+    default: Option<String>,
+    secret: bool,
+    confirm: bool,
+    multiline: bool,
+}
+
+fn ask_error(message: String) -> RenderError {
+    RenderError::from(RenderErrorReason::Other(message))
+}
+
+fn parse_confirmation(answer: &str) -> Option<bool> {
+    match answer.to_lowercase().as_str() {
+        "y" | "yes" | "true" => Some(true),
+        "n" | "no" | "false" => Some(false),
+        _ => None
+    }
+}
+
+impl Question {
+    fn from_helper(h: &Helper) -> Result<Self, RenderError> {
+        let text = h.params().first().ok_or(
+            ask_error("question not specified".to_string())
+        )?.render();
+
+        let flag = |name: &str| h.hash_get(name).is_some_and(|value| value.value().as_bool() == Some(true));
+
+        let question = Question {
+            text,
+            choices: h.hash_get("choices")
+                .map(|choices| choices.render().split(',').map(|choice| choice.trim().to_string()).collect())
+                .unwrap_or_default(),
+            default: h.hash_get("default").map(|default| match default.value() {
+                Value::Bool(true) => "yes".to_string(),
+                Value::Bool(false) => "no".to_string(),
+                default => default.render()
+            }),
+            secret: flag("secret"),
+            confirm: flag("confirm"),
+            multiline: flag("multiline"),
+        };
+
+        if let Some(default) = question.default.as_ref() && question.answer(default).is_err() {
+            return Err(ask_error(format!("default '{default}' is not a valid answer to \"{}\"", question.text)));
+        }
+
+        Ok(question)
+    }
+
+    fn display(&self, promptname: &str) -> String {
+        let mut display = format!("{promptname}> {}", self.text);
+
+        if self.confirm {
+            display.push_str(match self.default.as_deref().and_then(parse_confirmation) {
+                Some(true) => " [Y/n]",
+                Some(false) => " [y/N]",
+                None => " [y/n]"
+            });
+        } else {
+            if !self.choices.is_empty() {
+                display.push_str(&format!(" [{}]", self.choices.join("/")));
+            }
+            if let Some(default) = self.default.as_ref() && !self.secret {
+                display.push_str(&format!(" ({default})"));
+            }
+        }
+
+        if self.multiline {
+            display.push_str(" (end with Ctrl-D)\n");
+        } else if !display.ends_with(' ') {
+            display.push(' ');
+        }
+
+        display
+    }
+
+    /// The value of a non-empty answer, or why it is not valid.
+    fn answer(&self, answer: &str) -> Result<Value, String> {
+        if self.confirm {
+            return parse_confirmation(answer).map(Value::Bool)
+                .ok_or("Please answer yes or no".to_string());
+        }
+
+        if !self.choices.is_empty() {
+            return self.choices.iter()
+                .find(|choice| choice.eq_ignore_ascii_case(answer))
+                .map(|choice| Value::String(choice.clone()))
+                .ok_or(format!("Please answer one of: {}", self.choices.join(", ")));
+        }
+
+        Ok(Value::String(answer.to_string()))
+    }
+
+    /// Asks until a valid answer is given, falling back to the default on
+    /// an empty answer, or without a terminal.
+    fn ask(&self, promptname: &str, terminal: Option<&dyn Terminal>) -> Result<Value, RenderError> {
+        let default = || self.default.as_ref().map(|default| self.answer(default).unwrap());
+
+        let Some(terminal) = terminal else {
+            return default().ok_or(ask_error(
+                format!("Cannot ask \"{}\": input is not interactive and no default is given", self.text)
+            ));
+        };
+
+        loop {
+            let answer = terminal.prompt(&self.display(promptname), self.secret, self.multiline)?;
+
+            let Some(answer) = answer else {
+                return default().ok_or(ask_error(format!("No answer given to \"{}\"", self.text)));
+            };
+
+            let answer = if self.multiline {
+                answer.trim_end()
+            } else {
+                answer.trim()
+            };
+
+            if answer.is_empty() {
+                if let Some(default) = default() {
+                    return Ok(default);
+                }
+                if !self.confirm && self.choices.is_empty() {
+                    return Ok(Value::String(String::new()));
+                }
+            }
+
+            match self.answer(answer) {
+                Ok(value) => return Ok(value),
+                Err(message) => terminal.message(&message)?
+            }
+        }
+    }
+}
+
+impl HelperDef for AskHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
             &self,
             h: &Helper<'rc>,
             _: &'reg Handlebars<'reg>,
             _: &'rc Context,
             _: &mut RenderContext<'reg, 'rc>,
+        ) -> Result<ScopedJson<'rc>, RenderError> {
+        let question = Question::from_helper(h)?;
+        let mut value = question.ask(&self.promptname, self.terminal.as_deref())?;
+        strip_value_markers(&mut value);
+
+        Ok(ScopedJson::Derived(value))
+    }
+
+    // Answers are written as given, without escaping
+    fn call<'reg: 'rc, 'rc>(
+            &self,
+            h: &Helper<'rc>,
+            r: &'reg Handlebars<'reg>,
+            ctx: &'rc Context,
+            rc: &mut RenderContext<'reg, 'rc>,
             out: &mut dyn Output,
         ) -> HelperResult {
-        let question = h.params().first().ok_or(
-            RenderError::from(RenderErrorReason::Other("question not specified".to_string()))
-        )?.render();
+        let value = self.call_inner(h, r, ctx, rc)?;
+        out.write(&value.render())?;
 
-        let mut buffer = String::new();
+        Ok(())
+    }
+}
 
-        print!("{}> {}", &self.promptname, &question);
-        std::io::stdout().flush()?;
-        let mut inp = self.inp.lock().unwrap();
-        inp.read_line(&mut buffer)?;
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
 
+    use super::*;
+    use crate::dotprompt::helpers::ScriptedTerminal;
 
-        out.write(buffer.as_str().trim())?;
+    fn render(template: &str, answers: Option<&[&'static str]>) -> (Result<String, String>, Vec<String>) {
+        let terminal = answers.map(|answers| Arc::new(ScriptedTerminal::new(answers)));
 
-        Ok(())
+        let mut hbs = Handlebars::new();
+        hbs.set_strict_mode(true);
+        hbs.register_helper("ask", Box::new(AskHelper {
+            promptname: "deploy".to_string(),
+            terminal: terminal.clone().map(|terminal| terminal as Arc<dyn Terminal>)
+        }));
+
+        let result = hbs.render_template(template, &HashMap::<String, String>::new())
+            .map_err(|err| err.to_string());
+        let shown = terminal.map(|terminal| terminal.shown.lock().unwrap().clone()).unwrap_or_default();
+        (result, shown)
+    }
+
+    #[test]
+    fn test_choices_and_default() {
+        let template = r#"{{ask "Env?" choices="dev,prod" default="dev"}}"#;
+
+        let (result, shown) = render(template, Some(&["staging", "PROD"]));
+        assert_eq!(result, Ok("prod".to_string()));
+        assert_eq!(shown, vec!["deploy> Env? [dev/prod] (dev) ", "Please answer one of: dev, prod", "deploy> Env? [dev/prod] (dev) "]);
+
+        assert_eq!(render(template, Some(&[""])).0, Ok("dev".to_string()));
+        assert_eq!(render(template, None).0, Ok("dev".to_string()));
+    }
+
+    #[test]
+    fn test_free_text() {
+        assert_eq!(render(r#"{{ask "Name?"}}"#, Some(&["  R&D \n"])).0, Ok("R&D".to_string()));
+        assert_eq!(render(r#"{{ask "Notes?" multiline=true}}"#, Some(&["a\n\nb\n"])).0, Ok("a\n\nb".to_string()));
+        assert!(render(r#"{{ask "Name?"}}"#, Some(&[])).0.unwrap_err().contains("No answer given to \"Name?\""));
+    }
+
+    #[test]
+    fn test_confirm() {
+        let template = r#"{{#if (ask "Deploy?" confirm=true default=false)}}yes{{else}}no{{/if}}"#;
+
+        let (result, shown) = render(template, Some(&["maybe", "Y"]));
+        assert_eq!(result, Ok("yes".to_string()));
+        assert_eq!(shown[0], "deploy> Deploy? [y/N] ");
+        assert_eq!(shown[1], "Please answer yes or no");
+
+        assert_eq!(render(template, None).0, Ok("no".to_string()));
+    }
+
+    #[test]
+    fn test_not_interactive() {
+        let (result, _) = render(r#"{{ask "Token?" secret=true}}"#, None);
+        assert!(result.unwrap_err().contains("Cannot ask \"Token?\": input is not interactive and no default is given"));
+
+        let (result, _) = render(r#"{{ask "Env?" choices="dev,prod" default="qa"}}"#, None);
+        assert!(result.unwrap_err().contains("default 'qa' is not a valid answer"));
     }
 }
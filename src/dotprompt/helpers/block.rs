@@ -0,0 +1,23 @@
+use handlebars::*;
+
+// Named section of a template that prompts extending it can fill. Filling
+// happens when loading the prompt, so at render time only the content of the
+// block is rendered.
+pub struct BlockHelper;
+
+impl HelperDef for BlockHelper {
+    fn call<'reg: 'rc, 'rc>(
+            &self,
+            h: &Helper<'rc>,
+            r: &'reg Handlebars<'reg>,
+            ctx: &'rc Context,
+            rc: &mut RenderContext<'reg, 'rc>,
+            out: &mut dyn Output,
+        ) -> HelperResult {
+        if let Some(template) = h.template() {
+            template.render(r, ctx, rc, out)?;
+        }
+
+        Ok(())
+    }
+}
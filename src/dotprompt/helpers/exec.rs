@@ -1,7 +1,243 @@
 use handlebars::*;
+use serde_json::Value;
 
-use std::{io::Read, process::Command};
-pub struct ExecHelper;
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::dotprompt::helpers::prefetch::{NestedCall, Prefetched};
+use crate::dotprompt::messages::strip_markers;
+
+/// Runs a command, e.g. `{{exec "git" "diff" "--staged"}}`. Options are
+/// `shell=true`, `timeout="30s"`, `cwd=`, `stdin=`,
+/// `stderr="include|ignore|fail"`, `allow_failure=true`, `trim=true` and
+/// `parallel=true`, letting the command run ahead of rendering along with
+/// other independent calls.
+#[derive(Default)]
+pub struct ExecHelper {
+    // Results of the calls run ahead of rendering
+    pub prefetched: Arc<Prefetched>,
+}
+
+#[cfg(not(windows))]
+const SHELL: [&str; 2] = ["sh", "-c"];
+#[cfg(windows)]
+const SHELL: [&str; 2] = ["cmd", "/C"];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum Stderr {
+    // Merged with stdout as written
+    #[default]
+    Include,
+    Ignore,
+    // Writing to stderr fails the command
+    Fail
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct ExecOptions {
+    shell: bool,
+    timeout: Option<Duration>,
+    cwd: Option<String>,
+    stdin: Option<String>,
+    stderr: Stderr,
+    allow_failure: bool,
+    trim: bool,
+}
+
+fn exec_error(message: String) -> RenderError {
+    RenderError::from(RenderErrorReason::Other(message))
+}
+
+fn flag(key: &str, value: &Value) -> Result<bool, RenderError> {
+    match value {
+        Value::Bool(flag) => Ok(*flag),
+        Value::String(flag) if flag == "true" || flag == "false" => Ok(flag == "true"),
+        _ => Err(exec_error(format!("'{key}' of exec must be true or false")))
+    }
+}
+
+/// Duration given as seconds or with a unit, e.g. `30s`, `500ms` or `2m`.
+fn parse_timeout(value: &Value) -> Result<Duration, RenderError> {
+    let invalid = || exec_error(format!("invalid timeout '{}', expected e.g. 30s, 500ms or 2m", value.render()));
+
+    let (number, unit) = match value {
+        Value::Number(number) => (number.as_f64().ok_or_else(invalid)?, "s"),
+        Value::String(text) => {
+            let text = text.trim();
+            let split = text.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(text.len());
+            (text[..split].trim().parse::<f64>().map_err(|_| invalid())?, &text[split..])
+        }
+        _ => return Err(invalid())
+    };
+
+    let seconds = match unit {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => return Err(invalid())
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|_| invalid())
+}
+
+impl ExecOptions {
+    pub(crate) fn from_hash(hash: &BTreeMap<String, Value>) -> Result<Self, RenderError> {
+        let mut options = ExecOptions::default();
+
+        for (key, value) in hash {
+            match key.as_str() {
+                "shell" => options.shell = flag(key, value)?,
+                "timeout" => options.timeout = Some(parse_timeout(value)?),
+                "cwd" => options.cwd = Some(value.render()),
+                "stdin" => options.stdin = Some(value.render()),
+                "stderr" => options.stderr = match value.render().as_str() {
+                    "include" => Stderr::Include,
+                    "ignore" => Stderr::Ignore,
+                    "fail" => Stderr::Fail,
+                    other => return Err(exec_error(format!("invalid stderr '{other}', expected include, ignore or fail")))
+                },
+                "allow_failure" => options.allow_failure = flag(key, value)?,
+                "trim" => options.trim = flag(key, value)?,
+                // Handled when prefetching calls
+                "parallel" => { flag(key, value)?; },
+                key => return Err(exec_error(format!("unknown exec option '{key}'")))
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+fn read_all(mut reader: impl Read + Send + 'static) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut data = Vec::new();
+        let _ = reader.read_to_end(&mut data);
+        String::from_utf8_lossy(&data).into_owned()
+    })
+}
+
+// Kills the child along with the processes it started, in its process group
+fn kill(child: &mut Child) {
+    #[cfg(unix)]
+    let _ = Command::new("kill").args(["-KILL", "--", &format!("-{}", child.id())])
+        .stdout(Stdio::null()).stderr(Stdio::null()).status();
+    let _ = child.kill();
+}
+
+// Waits for the child to exit, killing it once the timeout passes
+fn wait(child: &mut Child, timeout: Option<Duration>) -> std::io::Result<Option<ExitStatus>> {
+    let Some(timeout) = timeout else {
+        return child.wait().map(Some);
+    };
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            kill(child);
+            child.wait()?;
+            return Ok(None);
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// Runs `cmd` with `args`, returning its output.
+pub(crate) fn run(cmd: &str, args: &[String], options: &ExecOptions) -> Result<String, RenderError> {
+    if cfg!(windows) && options.shell && !args.is_empty() {
+        return Err(exec_error(format!("Arguments cannot be given to shell command {cmd} on Windows")));
+    }
+
+    let mut command = if options.shell {
+        // Arguments are given to the shell command as $1, $2...
+        let mut command = Command::new(SHELL[0]);
+        command.arg(SHELL[1]).arg(cmd);
+        if !args.is_empty() {
+            command.arg(SHELL[0]).args(args);
+        }
+        command
+    } else {
+        let mut command = Command::new(cmd);
+        command.args(args);
+        command
+    };
+
+    if let Some(cwd) = options.cwd.as_ref() {
+        command.current_dir(cwd);
+    }
+    // Own process group, so a timeout also ends the processes it starts
+    #[cfg(unix)]
+    if options.timeout.is_some() {
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    }
+    command.stdin(if options.stdin.is_some() { Stdio::piped() } else { Stdio::null() });
+
+    let (reader, writer) = std::io::pipe()?;
+    let stderr_reader = if options.stderr == Stderr::Include {
+        command.stdout(writer.try_clone()?).stderr(writer);
+        None
+    } else {
+        let (stderr_reader, stderr_writer) = std::io::pipe()?;
+        command.stdout(writer).stderr(stderr_writer);
+        Some(stderr_reader)
+    };
+
+    let mut child = command.spawn()
+        .map_err(|err| exec_error(format!("Could not run command {cmd}: {err}")))?;
+    // Closes the parent's ends of the pipes, so reading them ends with the child
+    drop(command);
+
+    if let Some(input) = options.stdin.clone() && let Some(mut stdin) = child.stdin.take() {
+        thread::spawn(move || {
+            let _ = stdin.write_all(input.as_bytes());
+        });
+    }
+
+    let stdout = read_all(reader);
+    let stderr = stderr_reader.map(read_all);
+
+    // Output is not waited for after a timeout, as processes started by
+    // the command may still hold the pipes
+    let Some(status) = wait(&mut child, options.timeout)? else {
+        return Err(exec_error(format!("Command {cmd} timed out after {:?}", options.timeout.unwrap_or_default())));
+    };
+
+    let output = stdout.join().unwrap_or_default();
+    let stderr = stderr.map(|stderr| stderr.join().unwrap_or_default()).unwrap_or_default();
+
+    if !status.success() && !options.allow_failure {
+        let error_message = format!("Error executing command: {}, output was: {}{}", cmd, &output, &stderr);
+        return Err(exec_error(error_message));
+    }
+
+    if options.stderr == Stderr::Fail && !stderr.trim().is_empty() {
+        return Err(exec_error(format!("Command {cmd} wrote to stderr: {}", stderr.trim_end())));
+    }
+
+    let output = strip_markers(&output);
+    if options.trim {
+        Ok(output.trim().to_string())
+    } else {
+        Ok(output)
+    }
+}
+
+/// Runs the command of an exec call.
+pub(crate) fn run_call(call: &NestedCall) -> Result<String, RenderError> {
+    let mut params = call.params.iter().map(|param| param.render());
+    let cmd = params.next().ok_or(
+        exec_error("exec binary not specified".to_string())
+    )?;
+    let args = params.collect::<Vec<_>>();
+
+    run(&cmd, &args, &ExecOptions::from_hash(&call.hash)?)
+}
 
 impl HelperDef for ExecHelper {
     fn call<'reg: 'rc, 'rc>(
@@ -13,39 +249,78 @@ impl HelperDef for ExecHelper {
             out: &mut dyn Output,
         ) -> HelperResult {
 
-        let params = h.params();
-        let cmd = params.first().ok_or(
-            RenderError::from(RenderErrorReason::Other("exec binary not specified".to_string()))
-        )?.render();
-
-        let args: Vec<String> = params.iter().skip(1).map(|item| {
-            if item.is_value_missing() {
-                Err(RenderError::from(RenderErrorReason::Other(
-                    format!("Undefined variable: {}", item.relative_path().unwrap()))))
-            } else {
-                Ok(item.render())
-            }
-        }).collect::<Result<Vec<_>, _>>()?;
-
-        let (mut reader, writer) = std::io::pipe()?;
-
-        let child =  {
-            Command::new(&cmd)
-            .args(&args)
-            .stdout(writer.try_clone()?)
-            .stderr(writer)
-            .output()?
-        };
-
-        let mut output = String::new();
-        reader.read_to_string(&mut output)?;
-
-        if child.status.success() {
-            out.write(&output)?;
-            Ok(())
-        } else {
-            let error_message = format!("Error executing command: {}, output was: {}", &cmd, &output);
-            Err(RenderError::from(RenderErrorReason::Other(error_message)))
+        let call = NestedCall::from_helper(h);
+        if let Some(result) = self.prefetched.take(&call) {
+            out.write(&result?.0)?;
+            return Ok(());
+        }
+
+        if let Some(item) = h.params().iter().skip(1).find(|item| item.is_value_missing()) {
+            return Err(exec_error(format!("Undefined variable: {}", item.relative_path().unwrap())));
         }
+
+        out.write(&run_call(&call)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn render(template: &str) -> Result<String, String> {
+        let mut hbs = Handlebars::new();
+        hbs.set_strict_mode(true);
+        hbs.register_helper("exec", Box::new(ExecHelper::default()));
+
+        let data = HashMap::from([("diff", "-teh\n+the")]);
+        hbs.render_template(template, &data).map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn test_options() {
+        assert_eq!(render(r#"{{exec "echo" "a" "b"}}"#), Ok("a b\n".to_string()));
+        assert_eq!(render(r#"{{exec "echo $1 | tr a-z A-Z" "shout" shell=true trim=true}}"#), Ok("SHOUT".to_string()));
+        assert_eq!(render(r#"{{exec "grep" "+" stdin=diff}}"#), Ok("+the\n".to_string()));
+        assert_eq!(render(r#"{{exec "pwd" cwd="/" trim=true}}"#), Ok("/".to_string()));
+
+        let error = render(r#"{{exec "echo" tiemout="1s"}}"#).unwrap_err();
+        assert!(error.contains("unknown exec option 'tiemout'"), "{error}");
+    }
+
+    #[test]
+    fn test_stderr_and_failures() {
+        let template = |options: &str| format!(r#"{{{{exec "echo out; echo err >&2; exit 1" shell=true {options}}}}}"#);
+
+        let error = render(&template("")).unwrap_err();
+        assert!(error.contains("Error executing command"), "{error}");
+
+        assert_eq!(render(&template("allow_failure=true")), Ok("out\nerr\n".to_string()));
+        assert_eq!(render(&template("allow_failure=true stderr=\"ignore\"")), Ok("out\n".to_string()));
+
+        let error = render(&template("allow_failure=true stderr=\"fail\"")).unwrap_err();
+        assert!(error.contains("wrote to stderr: err"), "{error}");
+    }
+
+    #[test]
+    fn test_timeout() {
+        let start = Instant::now();
+        let error = render(r#"{{exec "sleep" "5" timeout="200ms"}}"#).unwrap_err();
+        assert!(error.contains("Command sleep timed out after 200ms"), "{error}");
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        // Processes started by the command end with it
+        let dir = tempfile::TempDir::new().unwrap();
+        let marker = dir.path().join("marker");
+        let template = format!(r#"{{{{exec "(sleep 0.5; touch $1) & wait" "{}" shell=true timeout="100ms"}}}}"#, marker.display());
+        assert!(render(&template).is_err());
+        thread::sleep(Duration::from_secs(1));
+        assert!(!marker.exists());
+
+        assert_eq!(parse_timeout(&Value::from(2)).unwrap(), Duration::from_secs(2));
+        assert_eq!(parse_timeout(&Value::from("1.5m")).unwrap(), Duration::from_secs(90));
+        assert!(parse_timeout(&Value::from("soon")).is_err());
     }
 }
@@ -0,0 +1,44 @@
+use handlebars::*;
+
+use crate::dotprompt::{media::MediaKind, messages::media_marker};
+
+// Attaches an image or document to the current message. Accepts either an
+// `image`/`media` input or a path.
+pub struct MediaHelper;
+
+impl HelperDef for MediaHelper {
+    fn call<'reg: 'rc, 'rc>(
+            &self,
+            h: &Helper<'rc>,
+            _: &'reg Handlebars<'reg>,
+            _: &'rc Context,
+            _: &mut RenderContext<'reg, 'rc>,
+            out: &mut dyn Output,
+        ) -> HelperResult {
+        let param = h.params().first().ok_or(
+            RenderError::from(RenderErrorReason::Other("media not specified".to_string()))
+        )?.value();
+
+        let paths = match param {
+            // Unset optional input
+            serde_json::Value::Null => Vec::new(),
+            serde_json::Value::Array(items) => items.iter().collect(),
+            value => vec![value]
+        };
+
+        for path in paths {
+            let path = path.get("path").unwrap_or(path).as_str().ok_or(
+                RenderError::from(RenderErrorReason::Other("media expects a path".to_string()))
+            )?;
+
+            if MediaKind::from_path(path).is_none() {
+                return Err(RenderError::from(RenderErrorReason::Other(
+                    format!("Unsupported media file: {path}"))));
+            }
+
+            out.write(&media_marker(path))?;
+        }
+
+        Ok(())
+    }
+}
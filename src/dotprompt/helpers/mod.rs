@@ -3,9 +3,24 @@ mod prompt;
 mod concat;
 mod stdin;
 mod ask;
+mod role;
+mod block;
+mod media;
+mod terminal;
+mod prefetch;
 
 pub use exec::ExecHelper;
 pub use prompt::PromptHelper;
 pub use concat::ConcatHelper;
-pub use stdin::StdinHelper;
+pub use stdin::{StdinHelper, SharedStdin, StdinError, HAS_STDIN};
 pub use ask::AskHelper;
+pub use role::RoleHelper;
+pub use block::BlockHelper;
+pub use media::MediaHelper;
+pub use terminal::{open_terminal, Terminal, Tty};
+pub use prefetch::{NestedCall, Prefetched};
+#[cfg(test)]
+pub(crate) use terminal::ScriptedTerminal;
+
+// Names of the helpers available to prompt templates
+pub const HELPERS: [&str; 9] = ["exec", "prompt", "concat", "stdin", "STDIN", "ask", "role", "media", "block"];
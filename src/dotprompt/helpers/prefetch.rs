@@ -0,0 +1,237 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+
+use futures::{stream, StreamExt};
+use handlebars::template::{HelperTemplate, Parameter, Template, TemplateElement};
+use handlebars::{Helper, Path, PathSeg, RenderError, RenderErrorReason};
+use serde_json::Value;
+
+use crate::dotprompt::helpers::{exec, PromptHelper};
+use crate::dotprompt::DotPrompt;
+
+// Helpers whose calls may run ahead of rendering
+const PREFETCHED: [&str; 2] = ["prompt", "exec"];
+
+type CallResult = Result<(String, Value), RenderError>;
+
+/// A helper call with its evaluated arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NestedCall {
+    pub helper: String,
+    pub params: Vec<Value>,
+    pub hash: BTreeMap<String, Value>,
+}
+
+impl NestedCall {
+    pub fn from_helper(h: &Helper) -> Self {
+        NestedCall {
+            helper: h.name().to_string(),
+            params: h.params().iter().map(|param| param.value().clone()).collect(),
+            hash: h.hash().iter().map(|(key, value)| (key.to_string(), value.value().clone())).collect()
+        }
+    }
+
+    fn key(&self) -> String {
+        serde_json::json!([self.helper, self.params, self.hash]).to_string()
+    }
+}
+
+fn helper_name(helper: &HelperTemplate) -> Option<&str> {
+    match &helper.name {
+        Parameter::Name(name) => Some(name),
+        Parameter::Path(Path::Relative((_, raw))) => Some(raw),
+        _ => None
+    }
+}
+
+// Literals and inputs, e.g. `"nginx"` or `server.name`
+fn resolve(parameter: &Parameter, inputs: &HashMap<String, Value>) -> Option<Value> {
+    match parameter {
+        Parameter::Literal(value) => Some(value.clone()),
+        Parameter::Path(Path::Relative((segs, _))) => {
+            let (PathSeg::Named(first), rest) = segs.split_first()? else {
+                return None;
+            };
+            rest.iter().try_fold(inputs.get(first)?, |value, seg| match seg {
+                PathSeg::Named(name) => value.get(name),
+                _ => None
+            }).cloned()
+        }
+        _ => None
+    }
+}
+
+// Commands may depend on each other, thus only run ahead when marked with
+// `parallel=true`
+fn parallel_exec(helper: &HelperTemplate) -> bool {
+    matches!(helper.hash.get("parallel"), Some(Parameter::Literal(Value::Bool(true))))
+}
+
+fn independent_call(helper: &HelperTemplate, inputs: &HashMap<String, Value>) -> Option<NestedCall> {
+    let name = helper_name(helper).filter(|name| PREFETCHED.contains(name))?;
+    if name == "exec" && !parallel_exec(helper) {
+        return None;
+    }
+
+    Some(NestedCall {
+        helper: name.to_string(),
+        params: helper.params.iter()
+            .map(|param| resolve(param, inputs))
+            .collect::<Option<_>>()?,
+        hash: helper.hash.iter()
+            .map(|(key, param)| Some((key.clone(), resolve(param, inputs)?)))
+            .collect::<Option<_>>()?
+    })
+}
+
+fn collect(template: &Template, inputs: &HashMap<String, Value>, calls: &mut Vec<NestedCall>) {
+    for element in &template.elements {
+        match element {
+            TemplateElement::Expression(helper) | TemplateElement::HtmlExpression(helper) => {
+                calls.extend(independent_call(helper, inputs));
+            }
+            // Role sections are always rendered, in the same context
+            TemplateElement::HelperBlock(helper) if helper_name(helper) == Some("role") => {
+                if let Some(template) = helper.template.as_ref() {
+                    collect(template, inputs, calls);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The `prompt` calls and `parallel=true` `exec` calls of the template that
+/// are always made, with arguments known before rendering: literals or
+/// inputs. Calls within conditional blocks or taking the results of other
+/// calls are left out.
+pub fn independent_calls(dotprompt: &DotPrompt, inputs: &HashMap<String, Value>) -> Vec<NestedCall> {
+    let mut calls = Vec::new();
+    if let Ok(template) = Template::compile(&dotprompt.template) {
+        collect(&template, inputs, &mut calls);
+    }
+    calls
+}
+
+/// Results of the calls run ahead of rendering, taken by the helpers in the
+/// order the calls appear in the template.
+#[derive(Default)]
+pub struct Prefetched {
+    results: Mutex<HashMap<String, VecDeque<CallResult>>>,
+}
+
+impl Prefetched {
+    pub fn take(&self, call: &NestedCall) -> Option<CallResult> {
+        self.results.lock().unwrap().get_mut(&call.key())?.pop_front()
+    }
+
+    async fn evaluate(call: &NestedCall, prompt_helper: &PromptHelper) -> CallResult {
+        if call.helper == "prompt" {
+            // Nested prompts prefetch their own calls
+            let evaluation: Pin<Box<dyn Future<Output = CallResult> + '_>> = Box::pin(prompt_helper.evaluate(call));
+            return evaluation.await;
+        }
+
+        let call = call.clone();
+        let output = tokio::task::spawn_blocking(move || exec::run_call(&call)).await
+            .map_err(|err| RenderError::from(RenderErrorReason::Other(err.to_string())))??;
+        Ok((output.clone(), Value::String(output)))
+    }
+
+    /// Runs the independent calls of the template, at most `max_parallel` at
+    /// a time. Nothing is run ahead when `max_parallel` is 1.
+    pub async fn run(&self, dotprompt: &DotPrompt, inputs: &HashMap<String, Value>, prompt_helper: &PromptHelper,
+        max_parallel: usize) {
+        let calls = independent_calls(dotprompt, inputs);
+        if max_parallel < 2 || calls.len() < 2 {
+            return;
+        }
+
+        let results = stream::iter(calls)
+            .map(|call| async move {
+                let result = Self::evaluate(&call, prompt_helper).await;
+                (call.key(), result)
+            })
+            .buffered(max_parallel)
+            .collect::<Vec<_>>().await;
+
+        let mut prefetched = self.results.lock().unwrap();
+        for (key, result) in results {
+            prefetched.entry(key).or_default().push_back(result);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::executor::{test_executor, ExecutionOutput, PromptInputs};
+    use crate::storage::promptfiles_mem::InMemoryPromptFilesStorage;
+    use crate::storage::PromptFilesStorage;
+
+    #[test]
+    fn test_independent_calls() {
+        let dotprompt = DotPrompt::try_from(r#"---
+input:
+  schema:
+    container: string
+---
+{{prompt "logs" container="postgres"}}
+{{#role "system"}}{{exec "date" "+%F" parallel=true}}{{/role}}
+{{exec "git" "stash"}}
+{{prompt "logs" container=container tail=100}}
+{{prompt "logs" container=missing}}
+{{prompt "summary" text=(prompt "logs" container="redis")}}
+{{#if container}}{{prompt "logs" container="nginx"}}{{/if}}"#).unwrap();
+
+        let inputs = HashMap::from([("container".to_string(), json!("web"))]);
+        let calls = independent_calls(&dotprompt, &inputs);
+
+        let call = |helper: &str, params: Vec<Value>, hash: &[(&str, Value)]| NestedCall {
+            helper: helper.to_string(),
+            params,
+            hash: hash.iter().map(|(key, value)| (key.to_string(), value.clone())).collect()
+        };
+        assert_eq!(calls, vec![
+            call("prompt", vec![json!("logs")], &[("container", json!("postgres"))]),
+            call("exec", vec![json!("date"), json!("+%F")], &[("parallel", json!(true))]),
+            call("prompt", vec![json!("logs")], &[("container", json!("web")), ("tail", json!(100))]),
+        ]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_concurrent_calls() {
+        let storage = InMemoryPromptFilesStorage::default();
+        storage.store("greet", r#"---
+input:
+  schema:
+    name: string
+---
+Hello {{name}}"#).unwrap();
+        // Each command creates its marker and waits for the other's, which
+        // only both find when run at the same time
+        storage.store("parent", r#"{{exec script dir "a" "b" shell=true parallel=true}}, {{exec script dir "b" "a" shell=true parallel=true}}, {{exec "printf" "c"}}
+{{prompt "greet" name="a"}}, {{prompt "greet" name="b"}}"#).unwrap();
+
+        let executor = test_executor(storage);
+        let dotprompt = executor.load_dotprompt("parent").unwrap();
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut inputs = PromptInputs::new();
+        inputs.insert("dir".to_string(), json!(dir.path().to_str().unwrap()));
+        inputs.insert("script".to_string(), json!(
+            "touch \"$1/$2\"; i=0; while [ ! -e \"$1/$3\" ] && [ $i -lt 100 ]; do sleep 0.05; i=$((i+1)); done; [ -e \"$1/$3\" ] && printf $2"
+        ));
+
+        let output = executor.clone().execute_dotprompt(&dotprompt, None, None, inputs, false, true).await;
+        let Ok(ExecutionOutput::RenderOnly(output)) = output else {
+            panic!("unexpected output");
+        };
+
+        assert_eq!(output, "a, b, c\nHello a, Hello b");
+    }
+}
@@ -1,70 +1,197 @@
 use std::{sync::Arc};
 
 use handlebars::*;
+use indexmap::IndexMap;
+use serde_json::Value;
 
-use crate::executor::{ExecutionOutput, Executor, ExecutorErorr, PromptInputs};
+use crate::config::appconfig::GlobalProviderProperties;
+use crate::config::resolver::{ResolvedGlobalProperties, ResolvedPropertySource};
+use crate::dotprompt::helpers::prefetch::{NestedCall, Prefetched};
+use crate::dotprompt::messages::strip_markers;
+use crate::dotprompt::renderers::argmatches::inputs_from_values;
+use crate::dotprompt::{DotPrompt, OutputFormat, SchemaElement};
+use crate::executor::{ExecutionOutput, Executor, ExecutorErorr};
+
+/// Runs a nested prompt, e.g. `{{prompt "classify" text=(STDIN) model="gpt-4o"}}`.
+/// Used as a subexpression it gives the parsed result of JSON prompts.
 pub struct PromptHelper {
     pub executor: Arc<Executor>,
+    // Name of the prompt using the helper
+    pub promptname: String,
     pub dry: bool,
     pub render_only: bool,
+    // Results of the calls run ahead of rendering
+    pub prefetched: Arc<Prefetched>,
 }
 
-impl PromptHelper {
-    async fn async_call<'reg: 'rc, 'rc>(
-            &self,
-            h: &Helper<'rc>,
-            out: &mut dyn Output,
-        ) -> HelperResult {
+fn prompt_error(message: String) -> RenderError {
+    RenderError::from(RenderErrorReason::Other(message))
+}
+
+fn number_param(key: &str, value: &Value) -> Result<f64, RenderError> {
+    match value {
+        Value::Number(number) => number.as_f64(),
+        Value::String(number) => number.trim().parse().ok(),
+        _ => None
+    }.ok_or(prompt_error(format!("'{key}' of a nested prompt must be a number")))
+}
+
+fn temperature_param(key: &str, value: &Value) -> Result<f32, RenderError> {
+    let temperature = number_param(key, value)?;
+    if !(0.0..=2.0).contains(&temperature) {
+        return Err(prompt_error(format!("'{key}' of a nested prompt must be a number between 0 and 2")));
+    }
+    Ok(temperature as f32)
+}
+
+fn max_tokens_param(key: &str, value: &Value) -> Result<u32, RenderError> {
+    let max_tokens = number_param(key, value)?;
+    if max_tokens.fract() != 0.0 || !(0.0..=u32::MAX as f64).contains(&max_tokens) {
+        return Err(prompt_error(format!("'{key}' of a nested prompt must be a non-negative integer")));
+    }
+    Ok(max_tokens as u32)
+}
+
+/// Value of a nested prompt's output: the parsed document for JSON prompts,
+/// the text otherwise.
+fn output_value(format: &OutputFormat, output: &str) -> Value {
+    match format {
+        OutputFormat::Json => serde_json::from_str(output)
+            .unwrap_or_else(|_| Value::String(output.to_string())),
+        _ => Value::String(output.to_string())
+    }
+}
+
+// Stands for the output of a JSON prompt that is not run, with the keys of
+// its output schema so templates using them still render
+fn placeholder_object(schema: &IndexMap<String, SchemaElement>) -> Value {
+    fn item(element: &SchemaElement) -> Value {
+        match element.data_type.as_str() {
+            "object" => placeholder_object(&element.properties),
+            "enum" => element.choices.first().map_or(Value::Null, |choice| Value::String(choice.clone())),
+            "number" | "integer" => Value::from(0),
+            "boolean" => Value::Bool(false),
+            "json" => Value::Object(Default::default()),
+            _ => Value::String(format!("[{}]", element.key))
+        }
+    }
+
+    schema.values().map(|element| {
+        let value = if element.list { Value::Array(vec![item(element)]) } else { item(element) };
+        (element.key.clone(), value)
+    }).collect()
+}
+
+/// Value of a nested prompt that is only rendered, or not run at all.
+fn placeholder_value(dotprompt: &DotPrompt, output: String) -> Value {
+    match dotprompt.frontmatter.output.format {
+        OutputFormat::Json if dotprompt.frontmatter.output.schema.is_empty() => Value::Null,
+        OutputFormat::Json => placeholder_object(&dotprompt.frontmatter.output.schema),
+        _ => Value::String(output)
+    }
+}
 
-        let promptname = h.params().first().ok_or(
-            RenderError::from(RenderErrorReason::Other("prompt name not specified".to_string()))
+impl PromptHelper {
+    pub async fn evaluate(&self, call: &NestedCall) -> Result<(String, Value), RenderError> {
+        let promptname = call.params.first().ok_or(
+            prompt_error("prompt name not specified".to_string())
         )?.render();
 
-        let params = h.hash();
+        let executor = Arc::new(self.executor.nested(&self.promptname, &promptname)
+            .map_err(|err| prompt_error(err.to_string()))?);
+
+        let dotprompt = executor.load_dotprompt(&promptname)
+            .map_err(|err| prompt_error(err.to_string()))?;
+        let schema = &dotprompt.frontmatter.input.schema;
 
-        let mut inputs = PromptInputs::new();
+        // model, temperature and max_tokens configure the nested prompt,
+        // unless it has inputs of these names
+        let mut values: IndexMap<String, Value> = IndexMap::new();
+        let mut properties = GlobalProviderProperties::default();
+        let mut requested_model = None;
 
-        for (k, v) in params {
-           inputs.insert(k.to_string(), v.value().clone());
+        for (key, value) in &call.hash {
+            match key.as_str() {
+                "model" if !schema.contains_key("model") => {
+                    requested_model = Some(value.render());
+                }
+                "temperature" if !schema.contains_key("temperature") => {
+                    properties.temperature = Some(temperature_param(key, value)?);
+                }
+                "max_tokens" if !schema.contains_key("max_tokens") => {
+                    properties.max_tokens = Some(max_tokens_param(key, value)?);
+                }
+                key => {
+                    values.insert(key.to_string(), value.clone());
+                }
+            }
         }
 
+        let inputs = inputs_from_values(&dotprompt, &values, &executor.appconfig.inputs)
+            .map_err(|err| prompt_error(format!("Invalid inputs for prompt '{promptname}': {err}")))?;
+
+        let overrides = (properties.temperature.is_some() || properties.max_tokens.is_some()).then(|| {
+            ResolvedGlobalProperties::from((&properties, ResolvedPropertySource::Dotprompt(self.promptname.clone())))
+        });
 
-        let result = self.executor.clone().execute(&promptname, None, None, inputs, self.dry, self.render_only).await.map_err(|err: ExecutorErorr| {
-            RenderError::from(RenderErrorReason::Other(err.to_string()))
+        let result = executor.execute_dotprompt(&dotprompt, overrides, requested_model,
+            inputs, self.dry, self.render_only).await.map_err(|err: ExecutorErorr| {
+            prompt_error(err.to_string())
         })?;
 
-        match result {
+        let output = match result {
             ExecutionOutput::StreamingOutput(mut stream) => {
-                let output = stream.sync_collect().await.map_err( |err|
-                    RenderError::from(RenderErrorReason::Other(err.to_string()))
-                )?;
-                out.write(output.as_str())?;
+                stream.sync_collect().await.map_err( |err|
+                    prompt_error(err.to_string())
+                )?
             },
             ExecutionOutput::StructuredStreamingOutput(mut stream) => {
-                let output = stream.sync_collect().await.map_err( |err|
-                    RenderError::from(RenderErrorReason::Other(err.to_string()))
-                )?;
-                out.write(output.as_str())?;
+                stream.sync_collect().await.map_err( |err|
+                    prompt_error(err.to_string())
+                )?
             }
-            ExecutionOutput::ImmediateOutput(output) => {
-                out.write(&output)?;
-            },
-            ExecutionOutput::Cached(output) => {
-                out.write(&output)?;
+            ExecutionOutput::ImmediateOutput(output) => output,
+            ExecutionOutput::Cached(output) => output,
+            ExecutionOutput::DryRun => {
+                return Ok((String::new(), placeholder_value(&dotprompt, String::new())));
             },
-            ExecutionOutput::DryRun => {},
             ExecutionOutput::RenderOnly(output) => {
-                out.write(&output)?;
+                return Ok((output.clone(), placeholder_value(&dotprompt, output)));
             }
         };
 
-        Ok(())
+        let output = strip_markers(&output);
+        let value = output_value(&dotprompt.frontmatter.output.format, &output);
+        Ok((output, value))
+    }
 
+    fn evaluate_blocking(&self, h: &Helper) -> Result<(String, Value), RenderError> {
+        let call = NestedCall::from_helper(h);
+        if let Some(result) = self.prefetched.take(&call) {
+            return result;
+        }
+
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(async {
+                self.evaluate(&call).await
+            })
+        })
     }
 }
 
 impl HelperDef for PromptHelper {
 
+    fn call_inner<'reg: 'rc, 'rc>(
+            &self,
+            h: &Helper<'rc>,
+            _: &'reg Handlebars<'reg>,
+            _: &'rc Context,
+            _: &mut RenderContext<'reg, 'rc>,
+        ) -> Result<ScopedJson<'rc>, RenderError> {
+        let (_, value) = self.evaluate_blocking(h)?;
+        Ok(ScopedJson::Derived(value))
+    }
+
     fn call<'reg: 'rc, 'rc>(
             &self,
             h: &Helper<'rc>,
@@ -73,10 +200,141 @@ impl HelperDef for PromptHelper {
             _: &mut RenderContext<'reg, 'rc>,
             out: &mut dyn Output,
         ) -> HelperResult {
-        tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
-                self.async_call(h, out).await
-            })
-        })
+        let (output, _) = self.evaluate_blocking(h)?;
+        out.write(&output)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::config::appconfig::AppConfig;
+    use crate::executor::{test_executor, test_executor_with, PromptInputs};
+    use crate::storage::promptfiles_mem::InMemoryPromptFilesStorage;
+    use crate::storage::PromptFilesStorage;
+
+    async fn render(parent: &str) -> Result<String, String> {
+        let storage = InMemoryPromptFilesStorage::default();
+        storage.store("classify", r#"---
+input:
+  schema:
+    text: string
+    labels?: string[]
+    limit?: integer = 3
+---
+Classify {{text}} in {{#each labels}}{{this}} {{/each}}up to {{limit}}"#).unwrap();
+        storage.store("parent", parent).unwrap();
+
+        let executor = test_executor(storage);
+        let dotprompt = executor.load_dotprompt("parent").unwrap();
+        match executor.clone().execute_dotprompt(&dotprompt, None, None, PromptInputs::new(), false, true).await {
+            Ok(ExecutionOutput::RenderOnly(output)) => Ok(output),
+            Ok(_) => Err("unexpected output".to_string()),
+            Err(err) => Err(err.to_string())
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_typed_inputs() {
+        let output = render(r#"{{prompt "classify" text="logs" labels=(concat "bug") limit="5"}}"#).await;
+        assert_eq!(output, Ok("Classify logs in bug up to 5".to_string()));
+
+        let output = render(r#"{{prompt "classify" text="logs"}}"#).await;
+        assert_eq!(output, Ok("Classify logs in up to 3".to_string()));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_invalid_inputs() {
+        let output = render(r#"{{prompt "classify" txt="logs"}}"#).await.unwrap_err();
+        assert!(output.contains("Invalid inputs for prompt 'classify': unknown input 'txt'"), "{output}");
+
+        let output = render(r#"{{prompt "classify" labels="a"}}"#).await.unwrap_err();
+        assert!(output.contains("Invalid inputs for prompt 'classify': the following required arguments were not provided"), "{output}");
+
+        let output = render(r#"{{prompt "classify" text="logs" limit="many"}}"#).await.unwrap_err();
+        assert!(output.contains("invalid value 'many' for '--limit <limit>'"), "{output}");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_overrides() {
+        let output = render(r#"{{prompt "classify" text="logs" model="ollama/llama3" temperature=0.2}}"#).await;
+        assert_eq!(output, Ok("Classify logs in up to 3".to_string()));
+
+        let output = render(r#"{{prompt "classify" text="logs" temperature="hot"}}"#).await.unwrap_err();
+        assert!(output.contains("'temperature' of a nested prompt must be a number"), "{output}");
+
+        let output = render(r#"{{prompt "classify" text="logs" temperature=3}}"#).await.unwrap_err();
+        assert!(output.contains("'temperature' of a nested prompt must be a number between 0 and 2"), "{output}");
+
+        for max_tokens in ["-1", "1.5"] {
+            let output = render(&format!(r#"{{{{prompt "classify" text="logs" max_tokens={max_tokens}}}}}"#)).await.unwrap_err();
+            assert!(output.contains("'max_tokens' of a nested prompt must be a non-negative integer"), "{output}");
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_nesting_limits() {
+        let storage = InMemoryPromptFilesStorage::default();
+        storage.store("a", r#"{{prompt "b"}}"#).unwrap();
+        storage.store("b", r#"{{prompt "a"}}"#).unwrap();
+        for level in 0..10 {
+            storage.store(&format!("level{level}"), &format!("{{{{prompt \"level{}\"}}}}", level + 1)).unwrap();
+        }
+
+        let executor = test_executor(storage);
+        let render = |promptname: &'static str| {
+            let executor = executor.clone();
+            async move {
+                let dotprompt = executor.load_dotprompt(promptname).unwrap();
+                executor.execute_dotprompt(&dotprompt, None, None, PromptInputs::new(), false, true).await
+                    .err().map(|err| err.to_string()).unwrap_or_default()
+            }
+        };
+
+        let error = render("a").await;
+        assert!(error.contains("Nested prompt cycle: a -> b -> a"), "{error}");
+
+        let error = render("level0").await;
+        assert!(error.contains("Nested prompts exceed the maximum depth of 8: level0 -> level1 -> level2 -> level3 \
+            -> level4 -> level5 -> level6 -> level7 -> level8 -> level9"), "{error}");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_placeholder_results() {
+        let storage = InMemoryPromptFilesStorage::default();
+        storage.store("severity", r#"---
+input:
+  schema:
+    text: string
+output:
+  format: json
+  schema:
+    severity(enum): [low, high]
+    reasons: string[]
+---
+Rate {{text}}"#).unwrap();
+        storage.store("parent", r#"{{#with (prompt "severity" text="logs")}}{{severity}}: {{#each reasons}}{{this}}{{/each}}{{/with}}"#).unwrap();
+
+        let appconfig = AppConfig::try_from("[providers]\ndefault = \"ollama/llama3\"\n[providers.ollama]\nendpoint = \"http://localhost:11434\"").unwrap();
+        let executor = test_executor_with(storage, appconfig);
+        let dotprompt = executor.load_dotprompt("parent").unwrap();
+        let output = executor.clone().execute_dotprompt(&dotprompt, None, None, PromptInputs::new(), false, true).await;
+        let Ok(ExecutionOutput::RenderOnly(output)) = output else {
+            panic!("unexpected output");
+        };
+        assert_eq!(output, "low: [reasons]");
+
+        let output = executor.clone().execute_dotprompt(&dotprompt, None, None, PromptInputs::new(), true, false).await;
+        assert!(matches!(output, Ok(ExecutionOutput::DryRun)), "{:?}", output.err());
+    }
+
+    #[test]
+    fn test_output_value() {
+        assert_eq!(output_value(&OutputFormat::Json, r#"{"severity": "high"}"#), json!({"severity": "high"}));
+        assert_eq!(output_value(&OutputFormat::Text, r#"{"severity": "high"}"#), json!(r#"{"severity": "high"}"#));
     }
 }
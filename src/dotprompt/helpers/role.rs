@@ -0,0 +1,36 @@
+use handlebars::*;
+
+use crate::dotprompt::messages::{role_block_close_marker, role_marker, MessageRole};
+
+pub struct RoleHelper;
+
+impl HelperDef for RoleHelper {
+    fn call<'reg: 'rc, 'rc>(
+            &self,
+            h: &Helper<'rc>,
+            r: &'reg Handlebars<'reg>,
+            ctx: &'rc Context,
+            rc: &mut RenderContext<'reg, 'rc>,
+            out: &mut dyn Output,
+        ) -> HelperResult {
+        let role = h.params().first().ok_or(
+            RenderError::from(RenderErrorReason::Other("role not specified".to_string()))
+        )?.render();
+
+        let role = MessageRole::try_from(role.as_str()).map_err(|err| {
+            RenderError::from(RenderErrorReason::Other(err))
+        })?;
+
+        out.write(&role_marker(role))?;
+
+        // Block form: {{#role "system"}}...{{/role}} applies to the enclosed
+        // content only. Inline form: {{role "assistant"}} applies to
+        // everything that follows until the next role marker.
+        if let Some(template) = h.template() {
+            template.render(r, ctx, rc, out)?;
+            out.write(&role_block_close_marker())?;
+        }
+
+        Ok(())
+    }
+}
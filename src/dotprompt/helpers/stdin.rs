@@ -1,13 +1,110 @@
-use std::{io::{BufReader, Read}, sync::Mutex};
+use std::io::{IsTerminal, Read};
+use std::sync::{Arc, Mutex};
 use log::debug;
 
 use handlebars::*;
+use serde_json::Value;
+use thiserror::Error;
 
-pub struct StdinHelper<R: Read> {
-    pub inp: Mutex<BufReader<R>>
+use crate::dotprompt::messages::strip_markers;
+use crate::dotprompt::DotPrompt;
+use crate::executor::PromptInputs;
+
+// Variable telling templates whether anything was piped to stdin
+pub const HAS_STDIN: &str = "has_stdin";
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum StdinError {
+    #[error("stdin exceeds the maximum size of {0} bytes")]
+    TooLarge(u64),
+
+    #[error("binary data on stdin is not supported")]
+    Binary,
+
+    #[error("Could not read stdin: {0}")]
+    Io(String),
+}
+
+struct State {
+    reader: Box<dyn Read + Send>,
+    captured: Option<Result<String, StdinError>>,
 }
 
-impl<R:Read> HelperDef for StdinHelper<R> {
+/// Stdin of a prompt execution, read once and shared by the helpers using
+/// it and by nested prompts.
+pub struct SharedStdin {
+    max_size: u64,
+    interactive: bool,
+    state: Mutex<State>,
+}
+
+impl SharedStdin {
+    pub fn new(reader: impl Read + Send + 'static, max_size: u64) -> Self {
+        SharedStdin {
+            max_size,
+            interactive: false,
+            state: Mutex::new(State {
+                reader: Box::new(reader),
+                captured: None
+            })
+        }
+    }
+
+    pub fn from_stdin(max_size: u64) -> Self {
+        SharedStdin {
+            interactive: std::io::stdin().is_terminal(),
+            ..SharedStdin::new(std::io::stdin(), max_size)
+        }
+    }
+
+    fn capture(state: &mut State, max_size: u64) -> Result<String, StdinError> {
+        debug!("Capturing stdin");
+
+        // Read one byte past the limit to detect oversized input without
+        // loading it entirely.
+        let mut data = Vec::new();
+        state.reader.as_mut().take(max_size + 1).read_to_end(&mut data)
+            .map_err(|err| StdinError::Io(err.to_string()))?;
+
+        if data.len() as u64 > max_size {
+            return Err(StdinError::TooLarge(max_size));
+        }
+
+        if data.contains(&0) {
+            return Err(StdinError::Binary);
+        }
+
+        String::from_utf8(data).map_err(|_| StdinError::Binary)
+    }
+
+    /// Whole content of stdin, captured on first use.
+    pub fn content(&self) -> Result<String, StdinError> {
+        let mut state = self.state.lock().unwrap();
+        if state.captured.is_none() {
+            state.captured = Some(Self::capture(&mut state, self.max_size));
+        }
+        state.captured.clone().unwrap()
+    }
+
+    /// Whether something was piped to stdin, including unreadable input
+    /// reported when used. A terminal is never read.
+    pub fn has_content(&self) -> bool {
+        !self.interactive && !matches!(self.content(), Ok(content) if content.is_empty())
+    }
+
+    /// Sets `has_stdin` for templates using it, unless an input has this name.
+    pub fn insert_has_stdin(&self, dotprompt: &DotPrompt, inputs: &mut PromptInputs) {
+        if dotprompt.uses_variable(HAS_STDIN) && !inputs.map.contains_key(HAS_STDIN) {
+            inputs.insert(HAS_STDIN.to_string(), Value::Bool(self.has_content()));
+        }
+    }
+}
+
+pub struct StdinHelper {
+    pub stdin: Arc<SharedStdin>
+}
+
+impl HelperDef for StdinHelper {
     fn call<'reg: 'rc, 'rc>(
             &self,
             h: &Helper<'rc>,
@@ -21,19 +118,53 @@ impl<R:Read> HelperDef for StdinHelper<R> {
         let concatenated = params.iter().map(|item| item.render()).collect::<Vec<String>>().join("");
         out.write(&concatenated)?;
 
-        let mut buffer = String::new();
-
-        let mut inp = self.inp.lock().unwrap();
-
-        debug!("Capturing stdin");
-        inp.read_to_string(&mut buffer)
+        let content = self.stdin.content()
             .map_err(|err| {
                 RenderError::from(RenderErrorReason::Other(err.to_string()))
             })?;
-        debug!("Done");
 
-        out.write(buffer.as_str())?;
+        out.write(&strip_markers(&content))?;
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_content_is_reused() {
+        let stdin = SharedStdin::new(Cursor::new("first\nsecond\n"), 100);
+
+        assert_eq!(stdin.content(), Ok("first\nsecond\n".to_string()));
+        assert_eq!(stdin.content(), Ok("first\nsecond\n".to_string()));
+        assert!(stdin.has_content());
+    }
+
+    #[test]
+    fn test_limits() {
+        assert_eq!(SharedStdin::new(Cursor::new("12345"), 4).content(), Err(StdinError::TooLarge(4)));
+        assert_eq!(SharedStdin::new(Cursor::new("1234"), 4).content(), Ok("1234".to_string()));
+        assert_eq!(SharedStdin::new(Cursor::new(b"PK\x03\x04\x00".to_vec()), 100).content(), Err(StdinError::Binary));
+        assert_eq!(SharedStdin::new(Cursor::new(b"\xff\xfe".to_vec()), 100).content(), Err(StdinError::Binary));
+        assert!(!SharedStdin::new(Cursor::new(""), 100).has_content());
+        assert!(SharedStdin::new(Cursor::new("12345"), 4).has_content());
+    }
+
+    #[test]
+    fn test_has_stdin() {
+        let dotprompt = DotPrompt::try_from("{{#if has_stdin}}{{STDIN}}{{else}}Nothing{{/if}}").unwrap();
+        let stdin = SharedStdin::new(Cursor::new("piped"), 100);
+
+        let mut inputs = PromptInputs::new();
+        stdin.insert_has_stdin(&dotprompt, &mut inputs);
+        assert_eq!(inputs.map.get(HAS_STDIN), Some(&Value::Bool(true)));
+
+        let mut inputs = PromptInputs::new();
+        stdin.insert_has_stdin(&DotPrompt::try_from("{{STDIN}}").unwrap(), &mut inputs);
+        assert!(inputs.map.is_empty());
+    }
+}
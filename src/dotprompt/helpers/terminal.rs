@@ -0,0 +1,187 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use log::error;
+
+#[cfg(unix)]
+const TTY_PATH: &str = "/dev/tty";
+#[cfg(windows)]
+const TTY_PATH: &str = "CONIN$";
+
+/// Where interactive questions are shown and answered.
+pub trait Terminal: Send + Sync {
+    /// Shows the question and reads the answer: a line, or everything up to
+    /// the end of input if `multiline`. `None` at the end of input.
+    fn prompt(&self, question: &str, secret: bool, multiline: bool) -> io::Result<Option<String>>;
+
+    fn message(&self, message: &str) -> io::Result<()>;
+}
+
+/// The controlling terminal of the process, which is available even when
+/// stdin and stdout are piped. Questions are written to stderr.
+pub struct Tty;
+
+impl Tty {
+    pub fn open() -> Option<Tty> {
+        File::open(TTY_PATH).ok().map(|_| Tty)
+    }
+}
+
+/// The terminal questions are asked on, unless input is disabled or the
+/// process has no terminal.
+pub fn open_terminal(no_input: bool) -> Option<Arc<dyn Terminal>> {
+    if no_input {
+        return None;
+    }
+    Tty::open().map(|tty| Arc::new(tty) as Arc<dyn Terminal>)
+}
+
+// Turns off echoing of typed characters for secrets
+#[cfg(unix)]
+fn set_echo(tty: &File, echo: bool) -> io::Result<()> {
+    let status = std::process::Command::new("stty")
+        .arg(if echo { "echo" } else { "-echo" })
+        .stdin(tty.try_clone()?)
+        .status()?;
+    if !status.success() {
+        return Err(io::Error::other(format!("stty failed with {status}")));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_echo(_: &File, _: bool) -> io::Result<()> {
+    Ok(())
+}
+
+// Whether echo is currently turned off by an EchoOff
+static ECHO_OFF: AtomicBool = AtomicBool::new(false);
+
+/// Keeps echo turned off while alive, and turns it back on when dropped,
+/// whichever way the question ends.
+struct EchoOff(File);
+
+impl EchoOff {
+    fn new(tty: &File) -> io::Result<Self> {
+        exit_on_interrupt();
+        let tty = tty.try_clone()?;
+        set_echo(&tty, false)?;
+        ECHO_OFF.store(true, Ordering::SeqCst);
+        Ok(EchoOff(tty))
+    }
+}
+
+impl Drop for EchoOff {
+    fn drop(&mut self) {
+        ECHO_OFF.store(false, Ordering::SeqCst);
+        if let Err(err) = set_echo(&self.0, true) {
+            error!("Could not turn echo back on: {err}");
+        }
+    }
+}
+
+// Ctrl-C terminates the process without dropping the EchoOff guard, so it is
+// handled instead by turning echo back on and exiting the same way. The
+// handler has its own thread and runtime, as the reading thread is blocked.
+#[cfg(unix)]
+fn exit_on_interrupt() {
+    use std::sync::Once;
+    use tokio::signal::unix::{signal, SignalKind};
+
+    static INSTALLED: Once = Once::new();
+    INSTALLED.call_once(|| {
+        let (installed_tx, installed_rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let Ok(runtime) = tokio::runtime::Builder::new_current_thread().enable_all().build() else {
+                return;
+            };
+            runtime.block_on(async {
+                let Ok(mut interrupt) = signal(SignalKind::interrupt()) else {
+                    return;
+                };
+                let _ = installed_tx.send(());
+                interrupt.recv().await;
+                if ECHO_OFF.load(Ordering::SeqCst) && let Ok(tty) = File::open(TTY_PATH) {
+                    let _ = set_echo(&tty, true);
+                    eprintln!();
+                }
+                std::process::exit(130);
+            });
+        });
+        let _ = installed_rx.recv();
+    });
+}
+
+#[cfg(not(unix))]
+fn exit_on_interrupt() {}
+
+impl Terminal for Tty {
+    fn prompt(&self, question: &str, secret: bool, multiline: bool) -> io::Result<Option<String>> {
+        let tty = OpenOptions::new().read(true).write(true).open(TTY_PATH)?;
+
+        eprint!("{question}");
+        io::stderr().flush()?;
+
+        let mut reader = BufReader::new(tty.try_clone()?);
+        let mut answer = String::new();
+
+        if multiline {
+            let mut line = String::new();
+            while reader.read_line(&mut line)? > 0 {
+                answer.push_str(&line);
+                line.clear();
+            }
+            return Ok(Some(answer));
+        }
+
+        let echo_off = if secret { Some(EchoOff::new(&tty)?) } else { None };
+        let read = reader.read_line(&mut answer);
+        if echo_off.is_some() {
+            drop(echo_off);
+            eprintln!();
+        }
+
+        match read? {
+            0 => Ok(None),
+            _ => Ok(Some(answer))
+        }
+    }
+
+    fn message(&self, message: &str) -> io::Result<()> {
+        eprintln!("{message}");
+        Ok(())
+    }
+}
+
+/// Terminal answering with the given answers, recording what it shows.
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct ScriptedTerminal {
+    pub answers: std::sync::Mutex<std::collections::VecDeque<&'static str>>,
+    pub shown: std::sync::Mutex<Vec<String>>,
+}
+
+#[cfg(test)]
+impl ScriptedTerminal {
+    pub fn new(answers: &[&'static str]) -> Self {
+        ScriptedTerminal {
+            answers: std::sync::Mutex::new(answers.iter().copied().collect()),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+impl Terminal for ScriptedTerminal {
+    fn prompt(&self, question: &str, _: bool, _: bool) -> io::Result<Option<String>> {
+        self.shown.lock().unwrap().push(question.to_string());
+        Ok(self.answers.lock().unwrap().pop_front().map(|answer| answer.to_string()))
+    }
+
+    fn message(&self, message: &str) -> io::Result<()> {
+        self.shown.lock().unwrap().push(message.to_string());
+        Ok(())
+    }
+}
@@ -0,0 +1,423 @@
+use std::collections::HashSet;
+
+use handlebars::template::{BlockParam, DecoratorTemplate, HelperTemplate, Parameter, Template, TemplateElement};
+use handlebars::{Path, PathSeg};
+use serde::Serialize;
+
+use crate::dotprompt::helpers::{HAS_STDIN, HELPERS};
+use crate::dotprompt::DotPrompt;
+
+// Helpers built into handlebars
+const BUILTIN_HELPERS: [&str; 17] = [
+    "if", "unless", "each", "with", "lookup", "raw", "log",
+    "eq", "ne", "gt", "gte", "lt", "lte", "and", "or", "not", "len"
+];
+
+// Helpers whose block is rendered with another context
+const CONTEXT_HELPERS: [&str; 2] = ["each", "with"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Finding {
+    pub severity: Severity,
+    pub message: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+/// Name of a prompt or binary referenced by a template, with the position
+/// of the reference.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference {
+    pub name: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+/// Results of the static analysis of a prompt's templates. References to
+/// other prompts and to binaries are collected to be checked by the caller.
+#[derive(Debug, Default, PartialEq)]
+pub struct Lint {
+    pub findings: Vec<Finding>,
+    pub prompts: Vec<Reference>,
+    pub binaries: Vec<Reference>,
+}
+
+struct Walker<'a> {
+    dotprompt: &'a DotPrompt,
+    lint: Lint,
+    used: HashSet<String>,
+    // Line of the prompt file the template starts at, if known
+    line_offset: Option<usize>,
+    partials: Vec<String>,
+}
+
+struct Scope<'s> {
+    declared: &'s HashSet<String>,
+    locals: Vec<String>,
+    // Inside blocks rendered with another context, e.g. each or with
+    changed_context: bool,
+    // Positions are only known within the prompt's own template
+    positioned: bool,
+}
+
+impl Walker<'_> {
+    fn position(&self, scope: &Scope, line: usize, column: usize) -> (Option<usize>, Option<usize>) {
+        match self.line_offset {
+            Some(offset) if scope.positioned => (Some(offset + line - 1), Some(column)),
+            _ => (None, None)
+        }
+    }
+
+    fn push(&mut self, severity: Severity, message: String, position: (Option<usize>, Option<usize>)) {
+        let finding = Finding {
+            severity,
+            message,
+            line: position.0,
+            column: position.1
+        };
+        if !self.lint.findings.contains(&finding) {
+            self.lint.findings.push(finding);
+        }
+    }
+
+    fn template(&mut self, template: &Template, scope: &Scope) {
+        for (idx, element) in template.elements.iter().enumerate() {
+            let (line, column) = template.mapping.get(idx)
+                .map(|mapping| (mapping.0, mapping.1))
+                .unwrap_or((1, 1));
+            let position = self.position(scope, line, column);
+            self.element(element, scope, position);
+        }
+    }
+
+    fn element(&mut self, element: &TemplateElement, scope: &Scope, position: (Option<usize>, Option<usize>)) {
+        match element {
+            TemplateElement::Expression(helper) | TemplateElement::HtmlExpression(helper)
+                | TemplateElement::HelperBlock(helper) => self.helper(helper, scope, position),
+            TemplateElement::PartialExpression(partial) | TemplateElement::PartialBlock(partial) => {
+                self.partial(partial, scope, position)
+            }
+            TemplateElement::DecoratorExpression(decorator) | TemplateElement::DecoratorBlock(decorator) => {
+                self.parameters(decorator.params.iter().chain(decorator.hash.values()), scope, position);
+                if let Some(template) = decorator.template.as_ref() {
+                    self.template(template, scope);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn helper(&mut self, helper: &HelperTemplate, scope: &Scope, position: (Option<usize>, Option<usize>)) {
+        let has_arguments = helper.block || !helper.params.is_empty() || !helper.hash.is_empty();
+
+        let name = match &helper.name {
+            Parameter::Name(name) => Some(name.as_str()),
+            // A simple expression, e.g. {{words}} or {{stdin}}
+            Parameter::Path(Path::Relative((segs, raw))) if segs.len() == 1 && is_known_helper(raw) => Some(raw.as_str()),
+            Parameter::Path(path) if !has_arguments => {
+                self.path(path, scope, position);
+                None
+            }
+            Parameter::Path(Path::Relative((_, raw))) => Some(raw.as_str()),
+            parameter => {
+                self.parameters([parameter], scope, position);
+                None
+            }
+        };
+
+        if let Some(name) = name {
+            if !is_known_helper(name) {
+                self.push(Severity::Error, format!("unknown helper '{name}'"), position);
+            }
+            self.references(name, helper, position);
+        }
+
+        self.parameters(helper.params.iter().chain(helper.hash.values()), scope, position);
+
+        let changed_context = scope.changed_context || name.is_some_and(|name| CONTEXT_HELPERS.contains(&name));
+        let mut locals = scope.locals.clone();
+        match helper.block_param.as_ref() {
+            Some(BlockParam::Single(param)) => locals.extend(parameter_name(param)),
+            Some(BlockParam::Pair((first, second))) => {
+                locals.extend(parameter_name(first));
+                locals.extend(parameter_name(second));
+            }
+            _ => {}
+        }
+
+        let inner = Scope {
+            declared: scope.declared,
+            locals,
+            changed_context,
+            positioned: scope.positioned
+        };
+        if let Some(template) = helper.template.as_ref() {
+            self.template(template, &inner);
+        }
+        // The else branch of each is rendered in the outer context
+        if let Some(inverse) = helper.inverse.as_ref() {
+            self.template(inverse, scope);
+        }
+    }
+
+    // Prompts and binaries given as literals to the prompt and exec helpers
+    fn references(&mut self, name: &str, helper: &HelperTemplate, position: (Option<usize>, Option<usize>)) {
+        let Some(Parameter::Literal(serde_json::Value::String(target))) = helper.params.first() else {
+            return;
+        };
+        let reference = Reference {
+            name: target.clone(),
+            line: position.0,
+            column: position.1
+        };
+        // Shell commands are not binaries
+        let shell = matches!(helper.hash.get("shell"), Some(Parameter::Literal(serde_json::Value::Bool(true))));
+        match name {
+            "prompt" => self.lint.prompts.push(reference),
+            "exec" if !shell => self.lint.binaries.push(reference),
+            _ => {}
+        }
+    }
+
+    fn partial(&mut self, partial: &DecoratorTemplate, scope: &Scope, position: (Option<usize>, Option<usize>)) {
+        self.parameters(partial.params.iter().chain(partial.hash.values()), scope, position);
+
+        let name = match &partial.name {
+            Parameter::Name(name) => name.clone(),
+            Parameter::Path(Path::Relative((_, raw))) => raw.clone(),
+            _ => return
+        };
+
+        // Partial blocks are rendered as fallback of missing partials
+        if let Some(template) = partial.template.as_ref() {
+            self.template(template, scope);
+        }
+
+        let Some(content) = self.dotprompt.partials.get(&name) else {
+            if partial.template.is_none() {
+                self.push(Severity::Error, format!("unknown partial '{name}'"), position);
+            }
+            return;
+        };
+
+        if self.partials.contains(&name) {
+            return;
+        }
+
+        match Template::compile(content) {
+            Ok(template) => {
+                // Hash parameters are variables of the partial
+                let mut declared = scope.declared.clone();
+                declared.extend(partial.hash.keys().cloned());
+                let inner = Scope {
+                    declared: &declared,
+                    locals: scope.locals.clone(),
+                    changed_context: scope.changed_context,
+                    positioned: false
+                };
+                self.partials.push(name);
+                self.template(&template, &inner);
+                self.partials.pop();
+            }
+            Err(err) => self.push(Severity::Error, format!("partial '{name}' is not a valid template: {err}"), position)
+        }
+    }
+
+    fn parameters<'p>(&mut self, parameters: impl IntoIterator<Item = &'p Parameter>, scope: &Scope, position: (Option<usize>, Option<usize>)) {
+        for parameter in parameters {
+            match parameter {
+                Parameter::Path(path) => self.path(path, scope, position),
+                Parameter::Subexpression(subexpression) => self.element(&subexpression.element, scope, position),
+                _ => {}
+            }
+        }
+    }
+
+    fn path(&mut self, path: &Path, scope: &Scope, position: (Option<usize>, Option<usize>)) {
+        let Path::Relative((segs, raw)) = path else {
+            // Local variables like @index
+            return;
+        };
+
+        // @root.name refers to the inputs whatever the context
+        let (root, changed_context) = match segs.as_slice() {
+            [PathSeg::Named(name), ..] => (name.as_str(), scope.changed_context),
+            [PathSeg::Ruled(_), PathSeg::Named(name), ..] if raw.starts_with("@root") => (name.as_str(), false),
+            _ => return
+        };
+
+        if changed_context || root == HAS_STDIN || scope.locals.iter().any(|local| local == root) {
+            return;
+        }
+
+        self.used.insert(root.to_string());
+        if !scope.declared.contains(root) {
+            self.push(Severity::Error, format!("variable '{root}' is not declared in the input schema"), position);
+        }
+    }
+}
+
+fn is_known_helper(name: &str) -> bool {
+    HELPERS.contains(&name) || BUILTIN_HELPERS.contains(&name)
+}
+
+fn parameter_name(parameter: &Parameter) -> Option<String> {
+    match parameter {
+        Parameter::Name(name) => Some(name.clone()),
+        Parameter::Path(Path::Relative((_, raw))) => Some(raw.clone()),
+        _ => None
+    }
+}
+
+/// Line of the prompt file at which its template starts.
+pub fn template_line_offset(promptdata: &str, template: &str) -> Option<usize> {
+    let first_line = template.lines().next()?;
+    let start = promptdata.rfind(template).or_else(|| promptdata.rfind(first_line))?;
+    Some(promptdata[..start].lines().count() + 1)
+}
+
+/// Checks the template and system prompt of a prompt against its input
+/// schema and the helpers and partials available. `line_offset` is the line
+/// its template starts at in the prompt file, to report positions.
+pub fn lint(dotprompt: &DotPrompt, line_offset: Option<usize>) -> Lint {
+    let declared = dotprompt.frontmatter.input.schema.keys().cloned().collect::<HashSet<_>>();
+
+    let mut walker = Walker {
+        dotprompt,
+        lint: Lint::default(),
+        used: HashSet::new(),
+        line_offset,
+        partials: Vec::new(),
+    };
+
+    let system = dotprompt.frontmatter.config.as_ref().and_then(|config| config.system.as_ref());
+    let templates = [(Some(&dotprompt.template), true), (system, false)];
+
+    for (source, positioned) in templates {
+        let Some(source) = source else {
+            continue;
+        };
+        let scope = Scope {
+            declared: &declared,
+            locals: Vec::new(),
+            changed_context: false,
+            positioned
+        };
+        match Template::compile(source) {
+            Ok(template) => walker.template(&template, &scope),
+            Err(err) => walker.push(Severity::Error, format!("invalid template: {err}"), (None, None))
+        }
+    }
+
+    for key in dotprompt.frontmatter.input.schema.keys() {
+        if !walker.used.contains(key) {
+            walker.push(Severity::Warning, format!("input '{key}' is declared but never used"), (None, None));
+        }
+    }
+
+    walker.lint
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn messages(lint: &Lint) -> Vec<&str> {
+        lint.findings.iter().map(|finding| finding.message.as_str()).collect()
+    }
+
+    #[test]
+    fn test_clean_prompt() {
+        let dotprompt = DotPrompt::try_from(r#"---
+input:
+  schema:
+    words: integer
+    author: json
+    items: string[]
+    lang?: string
+config:
+  system: Answer in {{#if lang}}{{lang}}{{else}}English{{/if}}
+---
+Summarize in {{words}} words by {{author.name}}:
+{{#each items as |item|}}{{@index}} {{item}} {{this}} {{@root.words}}{{/each}}
+{{#if has_stdin}}{{stdin}}{{/if}}
+{{#role "system"}}{{concat "a" (exec "date")}}{{/role}}
+{{exec "ls | wc -l" shell=true}}"#).unwrap();
+
+        let lint = lint(&dotprompt, Some(5));
+        assert_eq!(lint.findings, Vec::new());
+        assert_eq!(lint.binaries, vec![Reference { name: "date".to_string(), line: Some(8), column: Some(19) }]);
+    }
+
+    #[test]
+    fn test_undeclared_and_unused() {
+        let dotprompt = DotPrompt::try_from(r#"---
+input:
+  schema:
+    words: integer
+    text: string
+---
+Summarize {{txt}} in
+{{#if (gt wrds 10)}}many{{/if}} words"#).unwrap();
+
+        let lint = lint(&dotprompt, Some(7));
+        assert_eq!(messages(&lint), vec![
+            "variable 'txt' is not declared in the input schema",
+            "variable 'wrds' is not declared in the input schema",
+            "input 'words' is declared but never used",
+            "input 'text' is declared but never used",
+        ]);
+        assert_eq!((lint.findings[0].line, lint.findings[0].column), (Some(7), Some(11)));
+        assert_eq!(lint.findings[1].line, Some(8));
+        assert_eq!(lint.findings[2].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn test_helpers_and_partials() {
+        let dotprompt = DotPrompt::try_from(r#"---
+input:
+  schema:
+    lang: string
+---
+{{upper lang}}
+{{#repeat}}x{{/repeat}}
+{{prompt "translate" text=lang}}
+{{> code-only kind=lang}}
+{{> missing}}"#).unwrap()
+            .with_partials(HashMap::from([
+                ("code-only".to_string(), "Output only {{kind}} code{{> code-only}}".to_string())
+            ]));
+
+        let lint = lint(&dotprompt, None);
+        assert_eq!(messages(&lint), vec![
+            "unknown helper 'upper'",
+            "unknown helper 'repeat'",
+            "unknown partial 'missing'",
+        ]);
+        assert_eq!(lint.findings[0].line, None);
+        assert_eq!(lint.prompts.iter().map(|p| p.name.as_str()).collect::<Vec<_>>(), vec!["translate"]);
+    }
+
+    #[test]
+    fn test_invalid_template() {
+        let dotprompt = DotPrompt::try_from("{{#if x}}unclosed").unwrap();
+        let lint = lint(&dotprompt, None);
+        assert!(lint.findings[0].message.starts_with("invalid template"));
+    }
+
+    #[test]
+    fn test_template_line_offset() {
+        let promptdata = "# comment\n---\nmodel: x\n---\n\nHello\nthere";
+        assert_eq!(template_line_offset(promptdata, "Hello\nthere"), Some(6));
+        assert_eq!(template_line_offset("Hello", "Hello"), Some(1));
+    }
+}
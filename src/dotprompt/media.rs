@@ -0,0 +1,53 @@
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MediaKind {
+    Png,
+    Jpeg,
+    Gif,
+    Webp,
+    Pdf
+}
+
+impl MediaKind {
+    /// Media kind of a file, as told by its extension.
+    pub fn from_path(path: &str) -> Option<Self> {
+        let extension = Path::new(path).extension()?.to_string_lossy().to_lowercase();
+        match extension.as_str() {
+            "png" => Some(MediaKind::Png),
+            "jpg" | "jpeg" => Some(MediaKind::Jpeg),
+            "gif" => Some(MediaKind::Gif),
+            "webp" => Some(MediaKind::Webp),
+            "pdf" => Some(MediaKind::Pdf),
+            _ => None
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            MediaKind::Png => "image/png",
+            MediaKind::Jpeg => "image/jpeg",
+            MediaKind::Gif => "image/gif",
+            MediaKind::Webp => "image/webp",
+            MediaKind::Pdf => "application/pdf",
+        }
+    }
+
+    pub fn is_image(&self) -> bool {
+        !matches!(self, MediaKind::Pdf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(MediaKind::from_path("shot.PNG"), Some(MediaKind::Png));
+        assert_eq!(MediaKind::from_path("/tmp/photo.jpeg"), Some(MediaKind::Jpeg));
+        assert_eq!(MediaKind::from_path("spec.pdf").map(|kind| kind.is_image()), Some(false));
+        assert_eq!(MediaKind::from_path("notes.txt"), None);
+        assert_eq!(MediaKind::from_path("pdf"), None);
+    }
+}
@@ -0,0 +1,365 @@
+use std::fmt;
+
+use serde_json::Value;
+
+// Markers written by the role helper into the rendered template. They are
+// picked up again by `PromptMessages::from` to build the final list of messages.
+pub const ROLE_MARKER_START: char = '\u{1e}';
+pub const ROLE_MARKER_END: char = '\u{1f}';
+const ROLE_BLOCK_CLOSE: &str = "/";
+const MEDIA_PREFIX: &str = "media:";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MessageRole {
+    System,
+    User,
+    Assistant
+}
+
+impl TryFrom<&str> for MessageRole {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.trim().to_lowercase().as_str() {
+            "system" => Ok(MessageRole::System),
+            "user" => Ok(MessageRole::User),
+            "assistant" => Ok(MessageRole::Assistant),
+            other => Err(format!("Unsupported role: {other}"))
+        }
+    }
+}
+
+impl fmt::Display for MessageRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MessageRole::System => write!(f, "system"),
+            MessageRole::User => write!(f, "user"),
+            MessageRole::Assistant => write!(f, "assistant"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptMessage {
+    pub role: MessageRole,
+    pub content: String,
+    // Paths of images or documents attached to the message
+    pub media: Vec<String>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptMessages {
+    pub messages: Vec<PromptMessage>
+}
+
+pub fn role_marker(role: MessageRole) -> String {
+    format!("{ROLE_MARKER_START}{role}{ROLE_MARKER_END}")
+}
+
+pub fn role_block_close_marker() -> String {
+    format!("{ROLE_MARKER_START}{ROLE_BLOCK_CLOSE}{ROLE_MARKER_END}")
+}
+
+pub fn media_marker(path: &str) -> String {
+    format!("{ROLE_MARKER_START}{MEDIA_PREFIX}{path}{ROLE_MARKER_END}")
+}
+
+/// Removes the marker characters from text not written by the template, e.g.
+/// inputs or command output, so it can not start role sections or attach media.
+pub fn strip_markers(text: &str) -> String {
+    text.replace([ROLE_MARKER_START, ROLE_MARKER_END], "")
+}
+
+/// Removes the marker characters from all strings of a value.
+pub fn strip_value_markers(value: &mut Value) {
+    match value {
+        Value::String(text) if text.contains([ROLE_MARKER_START, ROLE_MARKER_END]) => *text = strip_markers(text),
+        Value::Array(items) => items.iter_mut().for_each(strip_value_markers),
+        Value::Object(fields) => fields.values_mut().for_each(strip_value_markers),
+        _ => {}
+    }
+}
+
+impl PromptMessages {
+    /// Concatenated content of all system messages, if any.
+    pub fn system(&self) -> Option<String> {
+        let system = self.messages.iter()
+            .filter(|message| message.role == MessageRole::System)
+            .map(|message| message.content.as_str())
+            .collect::<Vec<_>>();
+
+        if system.is_empty() {
+            None
+        } else {
+            Some(system.join("\n\n"))
+        }
+    }
+
+    /// All messages except system messages, in order.
+    pub fn conversation(&self) -> impl Iterator<Item = &PromptMessage> {
+        self.messages.iter().filter(|message| message.role != MessageRole::System)
+    }
+
+    /// Paths of all attached media, in order.
+    pub fn media(&self) -> impl Iterator<Item = &str> {
+        self.messages.iter().flat_map(|message| message.media.iter().map(|path| path.as_str()))
+    }
+
+    pub fn is_single_user_message(&self) -> bool {
+        self.messages.len() == 1 && self.messages[0].role == MessageRole::User
+            && self.messages[0].media.is_empty()
+    }
+}
+
+impl From<&str> for PromptMessages {
+    /// Splits a rendered template into messages according to the role markers
+    /// found in it. Text outside any role section belongs to the user. A
+    /// template without any role markers results in a single user message
+    /// holding the rendered text as is.
+    fn from(rendered: &str) -> Self {
+        if !rendered.contains(ROLE_MARKER_START) {
+            return PromptMessages {
+                messages: vec![PromptMessage {
+                    role: MessageRole::User,
+                    content: rendered.to_string(),
+                    media: Vec::new()
+                }]
+            };
+        }
+
+        let mut messages: Vec<PromptMessage> = Vec::new();
+        let mut stack: Vec<MessageRole> = vec![MessageRole::User];
+
+        fn push(messages: &mut Vec<PromptMessage>, role: MessageRole, content: &str, media: Option<&str>) {
+            let content = content.trim();
+            if content.is_empty() && media.is_none() {
+                return;
+            }
+            let message = match messages.last_mut() {
+                Some(last) if last.role == role => last,
+                _ => {
+                    messages.push(PromptMessage {
+                        role,
+                        content: String::new(),
+                        media: Vec::new()
+                    });
+                    messages.last_mut().unwrap()
+                }
+            };
+            if !content.is_empty() {
+                if !message.content.is_empty() {
+                    message.content.push_str("\n\n");
+                }
+                message.content.push_str(content);
+            }
+            message.media.extend(media.map(|path| path.to_string()));
+        }
+
+        let mut rest = rendered;
+        while let Some(start) = rest.find(ROLE_MARKER_START) {
+            let current = *stack.last().unwrap_or(&MessageRole::User);
+            push(&mut messages, current, &rest[..start], None);
+
+            let after_start = &rest[start + ROLE_MARKER_START.len_utf8()..];
+            let Some(end) = after_start.find(ROLE_MARKER_END) else {
+                // Not one of our markers, keep the remaining text as is.
+                push(&mut messages, current, &rest[start..], None);
+                rest = "";
+                break;
+            };
+
+            let marker = &after_start[..end];
+            if marker == ROLE_BLOCK_CLOSE {
+                if stack.len() > 1 {
+                    stack.pop();
+                }
+            } else if let Some(path) = marker.strip_prefix(MEDIA_PREFIX) {
+                push(&mut messages, current, "", Some(path));
+            } else if let Ok(role) = MessageRole::try_from(marker) {
+                stack.push(role);
+            }
+            rest = &after_start[end + ROLE_MARKER_END.len_utf8()..];
+        }
+        push(&mut messages, *stack.last().unwrap_or(&MessageRole::User), rest, None);
+
+        PromptMessages { messages }
+    }
+}
+
+impl fmt::Display for PromptMessages {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_single_user_message() {
+            return write!(f, "{}", self.messages[0].content);
+        }
+
+        let mut first = true;
+        for message in &self.messages {
+            if !first {
+                writeln!(f)?;
+            }
+            first = false;
+            writeln!(f, "[{}]", message.role)?;
+            for path in &message.media {
+                writeln!(f, "[media: {path}]")?;
+            }
+            if !message.content.is_empty() {
+                writeln!(f, "{}", message.content)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use handlebars::HelperDef;
+
+    use super::*;
+    use crate::dotprompt::{helpers::RoleHelper, renderers::Render, DotPrompt};
+    use crate::executor::PromptInputs;
+
+    #[test]
+    fn test_no_markers_single_user_message() {
+        let messages = PromptMessages::from("  Hello there\n");
+        assert_eq!(messages.messages, vec![PromptMessage {
+            role: MessageRole::User,
+            content: "  Hello there\n".to_string(),
+            media: Vec::new()
+        }]);
+        assert!(messages.is_single_user_message());
+        assert_eq!(messages.system(), None);
+    }
+
+    #[test]
+    fn test_block_sections() {
+        let rendered = format!(
+            "{}You classify text{}\n{}I love it{}\n{}positive{}\nI hate it",
+            role_marker(MessageRole::System), role_block_close_marker(),
+            role_marker(MessageRole::User), role_block_close_marker(),
+            role_marker(MessageRole::Assistant), role_block_close_marker(),
+        );
+        let messages = PromptMessages::from(rendered.as_str());
+
+        assert_eq!(messages.system(), Some("You classify text".to_string()));
+        let conversation = messages.conversation()
+            .map(|message| (message.role, message.content.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(conversation, vec![
+            (MessageRole::User, "I love it"),
+            (MessageRole::Assistant, "positive"),
+            (MessageRole::User, "I hate it"),
+        ]);
+    }
+
+    #[test]
+    fn test_inline_markers() {
+        let rendered = format!(
+            "{}Be brief{}Question{}Answer",
+            role_marker(MessageRole::System),
+            role_marker(MessageRole::User),
+            role_marker(MessageRole::Assistant),
+        );
+        let messages = PromptMessages::from(rendered.as_str());
+        let roles = messages.messages.iter().map(|m| m.role).collect::<Vec<_>>();
+        assert_eq!(roles, vec![MessageRole::System, MessageRole::User, MessageRole::Assistant]);
+        assert_eq!(messages.messages[2].content, "Answer");
+    }
+
+    #[test]
+    fn test_consecutive_same_role_merged() {
+        let rendered = format!(
+            "First{}Second{}",
+            role_marker(MessageRole::User), role_block_close_marker()
+        );
+        let messages = PromptMessages::from(rendered.as_str());
+        assert_eq!(messages.messages.len(), 1);
+        assert_eq!(messages.messages[0].content, "First\n\nSecond");
+    }
+
+    #[test]
+    fn test_media_markers() {
+        let rendered = format!(
+            "{}Describe these{}{}a cat{}",
+            media_marker("shot.png"), media_marker("spec.pdf"),
+            role_marker(MessageRole::Assistant), role_block_close_marker()
+        );
+        let messages = PromptMessages::from(rendered.as_str());
+
+        assert_eq!(messages.messages.len(), 2);
+        assert_eq!(messages.messages[0].content, "Describe these");
+        assert_eq!(messages.messages[0].media, vec!["shot.png", "spec.pdf"]);
+        assert_eq!(messages.media().collect::<Vec<_>>(), vec!["shot.png", "spec.pdf"]);
+        assert!(!messages.is_single_user_message());
+        assert_eq!(messages.to_string(), "[user]
+[media: shot.png]
+[media: spec.pdf]
+Describe these
+
+[assistant]
+a cat
+");
+    }
+
+    #[test]
+    fn test_render_role_sections() {
+        let dotprompt = DotPrompt::try_from(r#"---
+input:
+  schema:
+    text: string
+---
+{{#role "system"}}Classify the sentiment of the text.{{/role}}
+{{#role "user"}}I love it{{/role}}
+{{#role "assistant"}}positive{{/role}}
+{{text}}"#).unwrap();
+
+        let mut inputs = PromptInputs::new();
+        inputs.insert("text".to_string(), "I hate it".into());
+        let role_helper: Box<dyn HelperDef + Send + Sync> = Box::new(RoleHelper);
+        let rendered = dotprompt.render(inputs, HashMap::from([("role", role_helper)])).unwrap();
+        let messages = PromptMessages::from(rendered.as_str());
+
+        assert_eq!(messages.to_string(), "[system]
+Classify the sentiment of the text.
+
+[user]
+I love it
+
+[assistant]
+positive
+
+[user]
+I hate it
+");
+    }
+
+    #[test]
+    fn test_render_frontmatter_system() {
+        let dotprompt = DotPrompt::try_from(r#"---
+input:
+  schema:
+    persona: string
+config:
+  system: You are a {{persona}}.
+---
+{{#role "system"}}Answer briefly.{{/role}}
+Hello"#).unwrap();
+
+        let mut inputs = PromptInputs::new();
+        inputs.insert("persona".to_string(), "pirate".into());
+        let role_helper: Box<dyn HelperDef + Send + Sync> = Box::new(RoleHelper);
+        let rendered = dotprompt.render(inputs, HashMap::from([("role", role_helper)])).unwrap();
+        let messages = PromptMessages::from(rendered.as_str());
+
+        assert_eq!(messages.system(), Some("You are a pirate.\n\nAnswer briefly.".to_string()));
+        assert_eq!(messages.conversation().map(|m| m.content.as_str()).collect::<Vec<_>>(), vec!["Hello"]);
+    }
+
+    #[test]
+    fn test_render_unknown_role() {
+        let dotprompt = DotPrompt::try_from(r#"{{#role "narrator"}}Once upon a time{{/role}}"#).unwrap();
+        let role_helper: Box<dyn HelperDef + Send + Sync> = Box::new(RoleHelper);
+        assert!(dotprompt.render(PromptInputs::new(), HashMap::from([("role", role_helper)])).is_err());
+    }
+}
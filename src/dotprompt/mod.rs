@@ -1,34 +1,80 @@
 pub mod renderers;
 pub mod helpers;
-use regex::Regex;
+pub mod messages;
+pub mod media;
+pub mod constraints;
+pub mod testcases;
+pub mod lint;
+pub mod diagnostic;
+use regex::{Regex, RegexBuilder};
 use thiserror::Error;
 
 use serde_yaml::Value;
 use serde::{Deserialize};
-use std::{collections::HashMap};
-use std::convert::TryFrom;
+use indexmap::IndexMap;
+use std::{collections::HashMap, convert::TryFrom};
 use anyhow::{Result};
 use serde_json::json;
 
+use crate::dotprompt::constraints::{take_constraints, Constraints};
+
 #[derive(Debug, Error)]
 pub enum ParseError {
     #[error("A template is required but not found")]
     MissingTemplate,
-    #[error("Error parsing frontmatter")]
+    #[error("Error parsing frontmatter: {0}")]
     ParseFrontmatterError(#[from] serde_yaml::Error),
     #[error("Frontmatter not well formed")]
     FrontmatterNotWellFormed,
-    #[error("Error parsing schema: {0}")]
-    ParseSchemaError(String),
-    #[error("Enum field '{0}' not well formed: {0}")]
+    #[error("Error parsing schema: {reason}")]
+    ParseSchemaError {
+        key: String,
+        reason: String
+    },
+    #[error("Enum field '{0}' not well formed: {1}")]
     EnumFieldNotWellFormed(String, String),
     #[error("Unsupported output format: {0}")]
     UnsupportedOutputFormat(String),
+    #[error("Cyclic extends chain: {0}")]
+    ExtendsCycle(String),
+    #[error("Could not extend prompt: {0}")]
+    ExtendsError(String),
+    #[error("In parent prompt '{name}': {source}")]
+    InParent {
+        name: String,
+        source: Box<ParseError>
+    },
+}
+
+impl ParseError {
+    fn schema(key: &str, reason: String) -> Self {
+        ParseError::ParseSchemaError {
+            key: key.to_string(),
+            reason
+        }
+    }
+
+    /// Frontmatter key the error is about, if any.
+    pub fn key(&self) -> Option<&str> {
+        match self {
+            ParseError::ParseSchemaError { key, .. } | ParseError::EnumFieldNotWellFormed(key, _) => Some(key),
+            ParseError::UnsupportedOutputFormat(_) => Some("format"),
+            ParseError::ExtendsCycle(_) | ParseError::ExtendsError(_) => Some("extends"),
+            _ => None
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
 struct Frontmatter {
+    pub extends: Option<String>,
+    pub description: Option<String>,
+    pub version: Option<Value>,
+    pub author: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub examples: Option<Vec<String>>,
     pub model: Option<String>,
+    pub interactive: Option<bool>,
     pub input: Option<Input>,
     pub output: Option<Output>,
     pub config: Option<FrontmatterConfig>
@@ -37,14 +83,21 @@ struct Frontmatter {
 #[derive(Debug, Deserialize, PartialEq, Default)]
 pub struct FrontmatterConfig {
     pub temperature: Option<f32>,
+    #[serde(alias = "max_tokens")]
     pub max_output_tokens: Option<u32>,
     pub cache_ttl: Option<u32>,
+    pub system: Option<String>,
+    pub stream: Option<bool>,
 }
 
 #[derive(Debug, Default, PartialEq)]
 pub struct ParsedFrontmatter {
     pub from_frontmatter: bool,
+    pub extends: Option<String>,
+    pub metadata: Metadata,
     pub model: Option<String>,
+    // Ask for missing required inputs instead of failing
+    pub interactive: bool,
     pub config: Option<FrontmatterConfig>,
     pub input: ParsedInput,
     pub output: ParsedOutput
@@ -55,6 +108,9 @@ impl ParsedFrontmatter {
         ParsedFrontmatter {
             model: Some(model.to_string()),
             from_frontmatter: false,
+            extends: None,
+            metadata: Metadata::default(),
+            interactive: false,
             input: ParsedInput::default(),
             output: ParsedOutput::default(),
             config: None
@@ -62,9 +118,20 @@ impl ParsedFrontmatter {
     }
 }
 
+/// Descriptive fields of a prompt, shown in help and listings.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Metadata {
+    pub description: Option<String>,
+    pub version: Option<String>,
+    pub author: Option<String>,
+    pub tags: Vec<String>,
+    // Example invocations
+    pub examples: Vec<String>,
+}
+
 #[derive(Debug, Default, PartialEq)]
 pub struct ParsedInput {
-    pub schema: HashMap<String, SchemaElement>
+    pub schema: IndexMap<String, SchemaElement>
 }
 
 #[derive(Debug, Default, PartialEq, Clone)]
@@ -77,7 +144,9 @@ pub enum OutputFormat {
 #[derive(Debug, Default, PartialEq)]
 pub struct ParsedOutput {
     pub format: OutputFormat,
-    pub schema: HashMap<String, SchemaElement>
+    pub schema: IndexMap<String, SchemaElement>,
+    // Number of times an invalid JSON response is sent back for correction
+    pub retries: Option<u32>
 }
 
 #[derive(Debug, PartialEq)]
@@ -87,25 +156,52 @@ pub struct SchemaElement {
     pub choices: Vec<String>,
     pub description: String,
     pub required: bool,
-    pub positional: bool
+    pub positional: bool,
+    pub list: bool,
+    pub default: Option<String>,
+    pub properties: IndexMap<String, SchemaElement>,
+    pub constraints: Constraints,
+    pub cli: CliOptions
 }
 
+/// Command line options of an input besides its long flag, declared in the
+/// frontmatter's `input.cli` section.
+#[derive(Debug, Deserialize, Default, PartialEq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct CliOptions {
+    pub short: Option<char>,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    pub env: Option<String>,
+}
+
+// Short flags taken by the general options of prompt commands
+const RESERVED_SHORT_FLAGS: [char; 3] = ['h', 'm', 'r'];
+// Long flags taken by the general options, besides those starting with `config-`
+const RESERVED_LONG_FLAGS: [&str; 5] = ["dry", "render", "interactive", "no-input", "help"];
+// Argument id of `--no-input`
+const NO_INPUT_ID: &str = "no_input";
+
 #[derive(Deserialize, Debug)]
 struct Input {
-    pub schema: Option<HashMap<String, Value>>,
+    pub schema: Option<IndexMap<String, Value>>,
+    pub cli: Option<IndexMap<String, CliOptions>>,
 }
 
 #[derive(Deserialize, Debug)]
 struct Output {
     pub format: Option<String>,
-    pub schema: Option<HashMap<String, Value>>,
+    pub schema: Option<IndexMap<String, Value>>,
+    pub retries: Option<u32>,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct DotPrompt {
     pub name: String,
     pub frontmatter: ParsedFrontmatter,
-    pub template: String
+    pub template: String,
+    // Partial templates available to the template, by name
+    pub partials: HashMap<String, String>
 }
 
 impl TryFrom<&str> for DotPrompt {
@@ -116,14 +212,47 @@ impl TryFrom<&str> for DotPrompt {
     }
 }
 
+/// Splits a schema value of the form `type [= default][, description]`.
+/// Defaults containing commas can be given in double quotes.
+fn split_schema_value(value: &str) -> (&str, Option<String>, &str) {
+    let value = value.trim();
+    let (head, description) = value.split_once(",")
+        .unwrap_or((value, ""));
+
+    let Some((data_type, _)) = head.split_once("=") else {
+        return (head.trim(), None, description);
+    };
+
+    let after_eq = value[data_type.len() + 1..].trim_start();
+    if let Some(quoted) = after_eq.strip_prefix('"') &&
+        let Some((default, rest)) = quoted.split_once('"') {
+        let description = rest.trim_start().strip_prefix(",").unwrap_or(rest);
+        return (data_type.trim(), Some(default.to_string()), description);
+    }
+
+    let default = head[data_type.len() + 1..].trim();
+    (data_type.trim(), Some(default.to_string()), description)
+}
+
 impl TryFrom<&mut Frontmatter> for ParsedFrontmatter {
     type Error = ParseError;
 
 
     fn try_from(fm: &mut Frontmatter) -> std::result::Result<Self, Self::Error> {
-        fn build_schema(inp: &HashMap<String, Value>) -> Result<HashMap<String, SchemaElement>, ParseError> {
-            let mut out: HashMap<String, SchemaElement> = HashMap::new();
-            let enum_regex = Regex::new(r"^([^()]+)\(enum(?:,\s*([^)]*))?\)$").unwrap();
+        fn nested_schema(key: &str, mapping: &serde_yaml::Mapping) -> Result<IndexMap<String, SchemaElement>, ParseError> {
+            let fields = mapping.iter()
+                .map(|(field, value)| match field {
+                    Value::String(field) => Ok((field.clone(), value.clone())),
+                    _ => Err(ParseError::schema(key,
+                        format!("Object field '{key}' must only have string keys")))
+                })
+                .collect::<Result<IndexMap<_, _>, _>>()?;
+            build_schema(&fields)
+        }
+
+        fn build_schema(inp: &IndexMap<String, Value>) -> Result<IndexMap<String, SchemaElement>, ParseError> {
+            let mut out: IndexMap<String, SchemaElement> = IndexMap::new();
+            let enum_regex = Regex::new(r"^([^()]+)\(enum(?:\s*=\s*([^,)]+))?(?:,\s*([^)]*))?\)$").unwrap();
             for (key, value) in inp {
                 let mut key_chars = key.chars();
                 let (required, positional) = if key.ends_with("?!") || key.ends_with("!?") {
@@ -144,16 +273,17 @@ impl TryFrom<&mut Frontmatter> for ParsedFrontmatter {
                 };
 
                 let sanitized_key = key_chars.as_str();
-                let (final_key, data_type, description, choices) =  {
+                let (final_key, data_type, description, choices, list, default, properties, constraints) =  {
 
                     let enum_data = enum_regex.captures(sanitized_key).and_then(|caps| {
                         let name = caps.get(1)?.as_str().to_string();
-                        let description = caps.get(2).map(|m| m.as_str().to_string());
-                        Some((name, description))
+                        let default = caps.get(2).map(|m| m.as_str().trim().to_string());
+                        let description = caps.get(3).map(|m| m.as_str().to_string());
+                        Some((name, default, description))
                     });
 
                     match (value, enum_data) {
-                        (Value::Sequence(value), Some((enum_name, enum_desc))) => {
+                        (Value::Sequence(value), Some((enum_name, enum_default, enum_desc))) => {
                             let choices = value.iter()
                                 .map(|value |
                                     serde_yaml::to_string(value)
@@ -161,17 +291,63 @@ impl TryFrom<&mut Frontmatter> for ParsedFrontmatter {
                                 )
                                 .collect::<Result<Vec<_>, _>>()?;
 
-                            (enum_name, "enum".to_string(), enum_desc.unwrap_or("".to_string()), choices)
+                            if let Some(default) = enum_default.as_ref() && !choices.contains(default) {
+                                return Err(ParseError::schema(&enum_name,
+                                    format!("Default '{default}' of enum field '{enum_name}' is not one of its choices")))
+                            }
+
+                            (enum_name, "enum".to_string(), enum_desc.unwrap_or("".to_string()), choices, false, enum_default, IndexMap::new(), Constraints::default())
                         }
-                        (Value::String(value), None) => {
-                            let value = value.trim();
-                            let (data_type, description) = value.split_once(",")
-                                .unwrap_or((value, ""));
+                        (Value::Sequence(value), None) => {
+                            // List in YAML form: `[type]` or `[type, description]`, where
+                            // type can also be a mapping describing objects.
+                            let mut items = value.iter();
+                            let (data_type, properties) = match items.next() {
+                                Some(Value::String(data_type)) => (data_type.trim().to_string(), IndexMap::new()),
+                                Some(Value::Mapping(mapping)) => ("object".to_string(), nested_schema(sanitized_key, mapping)?),
+                                _ => return Err(ParseError::schema(sanitized_key,
+                                    format!("List field '{sanitized_key}' must declare its item type")))
+                            };
+                            let description = items
+                                .map(|value|
+                                    serde_yaml::to_string(value)
+                                        .map(|strval| strval.trim().to_string())
+                                )
+                                .collect::<Result<Vec<_>, _>>()?
+                                .join(", ");
 
-                            (sanitized_key.to_string(), data_type.to_string(), description.to_string(), Vec::new())
+                            (sanitized_key.to_string(), data_type, description, Vec::new(), true, None, properties, Constraints::default())
+                        }
+                        (Value::Mapping(mapping), None) => {
+                            // Nested object
+                            let properties = nested_schema(sanitized_key, mapping)?;
+                            (sanitized_key.to_string(), "object".to_string(), String::new(), Vec::new(), false, None, properties, Constraints::default())
+                        }
+                        (Value::String(value), None) => {
+                            let (value, constraints) = take_constraints(sanitized_key, value)?;
+                            let (data_type, default, description) = split_schema_value(&value);
+
+                            // List in short form: `type[]`
+                            let (data_type, list) = match data_type.strip_suffix("[]") {
+                                Some(item_type) => (item_type.trim(), true),
+                                None => (data_type, false)
+                            };
+
+                            if default.is_some() && data_type == "boolean" {
+                                return Err(ParseError::schema(sanitized_key,
+                                    format!("Boolean field '{sanitized_key}' cannot have a default value")))
+                            }
+
+                            let constraints = match constraints {
+                                Some(constraints) => Constraints::parse(sanitized_key, data_type, &constraints)?,
+                                None => Constraints::default()
+                            };
+
+                            (sanitized_key.to_string(), data_type.to_string(), description.to_string(), Vec::new(), list, default, IndexMap::new(), constraints)
                         }
                         (_, _) => {
-                            return Err(ParseError::ParseSchemaError("Incompatible value for field".to_string()))
+                            return Err(ParseError::schema(sanitized_key,
+                                format!("Incompatible value for field '{sanitized_key}'")))
                         }
                     }
                 };
@@ -182,25 +358,97 @@ impl TryFrom<&mut Frontmatter> for ParsedFrontmatter {
                     description: description.trim().to_string(),
                     data_type: data_type.to_string(),
                     choices ,
-                    positional
+                    positional,
+                    list,
+                    default,
+                    properties,
+                    constraints,
+                    cli: CliOptions::default()
                 };
                 out.insert(final_key, input_schema_element);
             }
             Ok(out)
         }
 
+        // Positional inputs are matched in declaration order, thus only the
+        // last one may take several values, and required ones can not follow
+        // optional ones.
+        fn validate_positionals(schema: &IndexMap<String, SchemaElement>) -> Result<(), ParseError> {
+            let positionals = schema.values()
+                .filter(|element| element.positional)
+                .collect::<Vec<_>>();
+
+            for (idx, element) in positionals.iter().enumerate() {
+                let is_last = idx + 1 == positionals.len();
+                if element.list && !is_last {
+                    return Err(ParseError::schema(&element.key,
+                        format!("Positional list '{}' must be the last positional input", element.key)))
+                }
+                if element.required && positionals[..idx].iter().any(|previous| !previous.required) {
+                    return Err(ParseError::schema(&element.key,
+                        format!("Required positional '{}' can not follow an optional positional", element.key)))
+                }
+            }
+            Ok(())
+        }
+
         let input_schema = fm.input.as_ref().and_then(|inp| inp.schema.as_ref());
-        let parsed_input_schema = if let Some(schema) = input_schema {
+        let mut parsed_input_schema = if let Some(schema) = input_schema {
             build_schema(schema)?
         } else {
-            HashMap::new()
+            IndexMap::new()
         };
 
+        // Nested objects are only described in output schemas
+        if let Some(element) = parsed_input_schema.values().find(|element| element.data_type == "object") {
+            return Err(ParseError::schema(&element.key,
+                format!("Input '{}' can not be an object, use the json type instead", element.key)))
+        }
+        validate_positionals(&parsed_input_schema)?;
+
+        if let Some(key) = parsed_input_schema.keys()
+            .find(|key| RESERVED_LONG_FLAGS.contains(&key.as_str()) || *key == NO_INPUT_ID) {
+            return Err(ParseError::schema(key,
+                format!("Input '{key}' clashes with a general option of prompt commands")))
+        }
+
+        let cli_options = fm.input.as_mut().and_then(|inp| inp.cli.take()).unwrap_or_default();
+        let mut short_flags: Vec<char> = Vec::new();
+        let mut long_flags: Vec<String> = parsed_input_schema.keys().cloned().collect();
+        for (key, cli) in cli_options {
+            let element = parsed_input_schema.get_mut(&key).ok_or(ParseError::schema(&key,
+                format!("CLI options given for unknown input '{key}'")))?;
+
+            if element.positional && (cli.short.is_some() || !cli.aliases.is_empty()) {
+                return Err(ParseError::schema(&key,
+                    format!("Positional input '{key}' can not have a short flag or aliases")))
+            }
+
+            if let Some(short) = cli.short {
+                if !short.is_ascii_alphanumeric() || RESERVED_SHORT_FLAGS.contains(&short) || short_flags.contains(&short) {
+                    return Err(ParseError::schema(&key,
+                        format!("Short flag '-{short}' of input '{key}' is invalid or already taken")))
+                }
+                short_flags.push(short);
+            }
+
+            for alias in &cli.aliases {
+                if alias.is_empty() || alias.starts_with('-') || alias.starts_with("config-")
+                    || RESERVED_LONG_FLAGS.contains(&alias.as_str()) || long_flags.contains(alias) {
+                    return Err(ParseError::schema(&key,
+                        format!("Alias '--{alias}' of input '{key}' is invalid or already taken")))
+                }
+                long_flags.push(alias.clone());
+            }
+
+            element.cli = cli;
+        }
+
         let output_schema = fm.output.as_ref().and_then(|out| out.schema.as_ref());
         let parsed_output_schema = if let Some(schema) = output_schema {
             build_schema(schema)?
         } else {
-            HashMap::new()
+            IndexMap::new()
         };
 
         let output_format = fm.output.as_ref()
@@ -216,7 +464,19 @@ impl TryFrom<&mut Frontmatter> for ParsedFrontmatter {
 
         Ok(
             ParsedFrontmatter {
+                extends: fm.extends.take(),
+                metadata: Metadata {
+                    description: fm.description.take().map(|description| description.trim().to_string()),
+                    // Versions like 1.2 are numbers in YAML
+                    version: fm.version.take()
+                        .map(|version| serde_yaml::to_string(&version).map(|version| version.trim().to_string()))
+                        .transpose()?,
+                    author: fm.author.take(),
+                    tags: fm.tags.take().unwrap_or_default(),
+                    examples: fm.examples.take().unwrap_or_default(),
+                },
                 model: fm.model.take(),
+                interactive: fm.interactive.unwrap_or_default(),
                 config: fm.config.take(),
                 from_frontmatter: true,
                 input: ParsedInput {
@@ -224,7 +484,8 @@ impl TryFrom<&mut Frontmatter> for ParsedFrontmatter {
                 },
                 output: ParsedOutput {
                     format: output_format,
-                    schema: parsed_output_schema
+                    schema: parsed_output_schema,
+                    retries: fm.output.as_ref().and_then(|output| output.retries)
                 }
             }
         )
@@ -233,111 +494,339 @@ impl TryFrom<&mut Frontmatter> for ParsedFrontmatter {
     }
 }
 
+/// Sections of a prompt file. Line numbers are 1-based.
+#[derive(Debug, PartialEq)]
+pub(crate) struct PromptSections {
+    pub frontmatter: Option<String>,
+    // Line of the first frontmatter line, after the opening delimiter
+    pub frontmatter_line: usize,
+    pub template: String,
+}
+
+const DELIMITER: &str = "---";
+
+/// Index of the first line that is neither empty nor a comment.
+fn first_content_line(lines: &[&str]) -> Option<usize> {
+    lines.iter().position(|line| {
+        let trimmed = line.trim();
+        !(trimmed.starts_with("#") || trimmed.is_empty())
+    })
+}
+
+/// Line of the delimiter opening the frontmatter, if the prompt has one.
+pub(crate) fn opening_delimiter_line(promptdata: &str) -> Option<usize> {
+    let lines = promptdata.lines().collect::<Vec<_>>();
+    first_content_line(&lines)
+        .filter(|idx| lines[*idx].trim_start().starts_with(DELIMITER))
+        .map(|idx| idx + 1)
+}
+
+/// Scans a prompt file for its frontmatter, delimited by `---` lines, and
+/// its template. Leading empty and comment lines are skipped. The closing
+/// delimiter must be a line of its own starting at the first column, so
+/// that `---` within YAML values does not end the frontmatter.
+pub(crate) fn scan_sections(promptdata: &str) -> Result<PromptSections, ParseError> {
+    let lines = promptdata.lines().collect::<Vec<_>>();
+
+    let Some(start) = first_content_line(&lines) else {
+        return Ok(PromptSections {
+            frontmatter: None,
+            frontmatter_line: 1,
+            template: String::new()
+        })
+    };
+
+    if !lines[start].trim_start().starts_with(DELIMITER) {
+        // No frontmatter, which is okay.
+        return Ok(PromptSections {
+            frontmatter: None,
+            frontmatter_line: 1,
+            template: lines[start..].join("\n").trim_start().to_string()
+        })
+    }
+
+    // Nothing may follow the opening delimiter on its line
+    if lines[start].trim() != DELIMITER {
+        return Err(ParseError::FrontmatterNotWellFormed);
+    }
+
+    let close = lines[start + 1..].iter()
+        .position(|line| line.trim_end() == DELIMITER)
+        .map(|idx| start + 1 + idx)
+        .ok_or(ParseError::FrontmatterNotWellFormed)?;
+
+    Ok(PromptSections {
+        frontmatter: Some(lines[start + 1..close].join("\n")),
+        frontmatter_line: start + 2,
+        template: lines[close + 1..].join("\n").trim().to_string()
+    })
+}
+
+/// Splits the content of a prompt file into its raw frontmatter, if any, and
+/// its template.
+fn split_promptdata(promptdata: &str) -> Result<(Option<Value>, String), ParseError> {
+    let sections = scan_sections(promptdata)?;
+
+    let frontmatter = sections.frontmatter
+        .map(|frontmatter| serde_yaml::from_str::<Value>(&frontmatter))
+        .transpose()?;
+
+    Ok((frontmatter, sections.template))
+}
+
 impl TryFrom<(&str, &str)> for DotPrompt {
     type Error = ParseError;
 
     fn try_from(name_promptdata: (&str, &str)) -> Result<Self, Self::Error> {
 
         let (name, promptdata) = name_promptdata;
-        // Skip all lines starting with '#'
-        let s = promptdata.trim_start().lines()
-            .skip_while(
-                |line| {
-                    let trimmed = line.trim();
-                    trimmed.starts_with("#") || trimmed.is_empty()
+        let (frontmatter, template) = split_promptdata(promptdata)?;
+
+        DotPrompt::from_parts(name, frontmatter, template)
+    }
+}
+
+// Overridable sections of a template: {{#block "name"}}default{{/block}}
+fn block_regex() -> Regex {
+    RegexBuilder::new(r#"\{\{#block\s+"([^"]+)"\s*\}\}(.*?)\{\{/block\}\}"#)
+        .dot_matches_new_line(true)
+        .build()
+        .unwrap()
+}
+
+// Blocks end at the first `{{/block}}`, thus can not be nested
+fn check_not_nested(block_regex: &Regex, template: &str) -> Result<(), ParseError> {
+    match block_regex.captures_iter(template).find(|caps| caps[2].contains("{{#block")) {
+        Some(caps) => Err(ParseError::ExtendsError(format!("blocks can not be nested, as in block '{}'", &caps[1]))),
+        None => Ok(())
+    }
+}
+
+/// Fills the blocks of the parent template with the ones given in the child
+/// template. The child template may only consist of blocks.
+fn fill_blocks(parent_template: &str, child_template: &str) -> Result<String, ParseError> {
+    let block_regex = block_regex();
+    check_not_nested(&block_regex, parent_template)?;
+    check_not_nested(&block_regex, child_template)?;
+
+    let outside_blocks = block_regex.replace_all(child_template, "");
+    if !outside_blocks.trim().is_empty() {
+        return Err(ParseError::ExtendsError(
+            "a prompt extending another one may only contain blocks in its template".to_string()))
+    }
+
+    let parent_blocks = block_regex.captures_iter(parent_template)
+        .map(|caps| caps[1].to_string())
+        .collect::<Vec<_>>();
+
+    let mut overrides: HashMap<String, String> = HashMap::new();
+    for caps in block_regex.captures_iter(child_template) {
+        if !parent_blocks.contains(&caps[1].to_string()) {
+            return Err(ParseError::ExtendsError(format!("unknown block '{}'", &caps[1])))
+        }
+        overrides.insert(caps[1].to_string(), caps[2].to_string());
+    }
+
+    // Blocks are kept in the resulting template so that they can be filled
+    // again further down the chain.
+    Ok(block_regex.replace_all(parent_template, |caps: &regex::Captures| {
+        match overrides.get(&caps[1]) {
+            Some(content) => format!("{{{{#block \"{}\"}}}}{content}{{{{/block}}}}", &caps[1]),
+            None => caps[0].to_string()
+        }
+    }).into_owned())
+}
+
+/// Merges the child's frontmatter into the parent's. Mappings are merged
+/// recursively, except for schemas which are overridden as a whole.
+fn merge_frontmatter(parent: Value, child: Value) -> Value {
+    match (parent, child) {
+        (Value::Mapping(mut parent), Value::Mapping(child)) => {
+            for (key, value) in child {
+                match parent.get_mut(&key) {
+                    Some(parent_value) if key.as_str() != Some("schema") => {
+                        *parent_value = merge_frontmatter(parent_value.clone(), value);
+                    }
+                    _ => {
+                        parent.insert(key, value);
+                    }
                 }
-            )
-            .collect::<Vec<_>>()
-            .join("\n");
-
-        // Expect frontmatter to start with `---`
-        let (frontmatter, template) = if s.starts_with("---") {
-            // Split into at most 3 parts: before first ---, frontmatter, rest.
-            // Using splitn(3, "---") yields: ["", "<fm>", "<rest>"]
-            let sections: Vec<&str> = s.splitn(3,"---").collect();
-            // Need at least 3 sections for a properly closed block:
-            // [before, content, after]
-            if sections.len() < 3 {
-                return Err(ParseError::FrontmatterNotWellFormed);
             }
+            Value::Mapping(parent)
+        }
+        (parent, Value::Null) => parent,
+        (_, child) => child
+    }
+}
 
-            let mut parts = sections.iter();
+fn resolve_extends<E: From<ParseError>>(
+    name: &str,
+    promptdata: &str,
+    load: &impl Fn(&str) -> Result<String, E>,
+    chain: &mut Vec<String>
+) -> Result<(Option<Value>, String), E> {
+    if chain.iter().any(|visited| visited == name) {
+        chain.push(name.to_string());
+        return Err(ParseError::ExtendsCycle(chain.join(" -> ")).into())
+    }
+    chain.push(name.to_string());
 
-            // skip prefix (could be empty)
-            parts.next();
+    // Errors located in a parent are tagged with its name
+    let (frontmatter, template) = split_promptdata(promptdata).map_err(|err| match chain.len() {
+        1 => err,
+        _ => ParseError::InParent { name: name.to_string(), source: Box::new(err) }
+    })?;
 
-            let fm_str = parts
-                .next()
-                .ok_or(ParseError::FrontmatterNotWellFormed)?
-                .trim();
+    let Some(parent) = frontmatter.as_ref()
+        .and_then(|fm| fm.get("extends"))
+        .and_then(|extends| extends.as_str())
+        .map(|extends| extends.to_string()) else {
+        return Ok((frontmatter, template))
+    };
 
-            let template = parts
-                .next()
-                .ok_or(ParseError::MissingTemplate)?
-                .trim()
-                .to_string();
+    let parent_promptdata = load(&parent)?;
+    let (parent_frontmatter, parent_template) = resolve_extends(&parent, &parent_promptdata, load, chain)?;
 
-            // Now parse the frontmatter YAML into the typed struct
-            let fm: Frontmatter = serde_yaml::from_str(fm_str)?;
-            (Some(fm), template)
-        } else {
-            // No frontmatter, which is okay.
-            (None, s)
+    let frontmatter = merge_frontmatter(
+        parent_frontmatter.unwrap_or(Value::Null), frontmatter.unwrap_or(Value::Null));
+
+    Ok((Some(frontmatter), fill_blocks(&parent_template, &template)?))
+}
+
+impl DotPrompt {
+    fn from_parts(name: &str, frontmatter: Option<Value>, template: String) -> Result<Self, ParseError> {
+        let parsed_frontmatter = match frontmatter {
+            Some(frontmatter) if !frontmatter.is_null() => {
+                let mut fm: Frontmatter = serde_yaml::from_value(frontmatter)?;
+                ParsedFrontmatter::try_from(&mut fm)?
+            }
+            _ => ParsedFrontmatter::default()
         };
 
-        if template.trim().is_empty() {
+        // A prompt extending another one may consist of frontmatter only
+        if template.trim().is_empty() && parsed_frontmatter.extends.is_none() {
             return Err(ParseError::MissingTemplate);
         }
 
-        let parsed_frontmatter = frontmatter.map_or_else(
-            || Ok(ParsedFrontmatter::default()),
-            |mut fm| ParsedFrontmatter::try_from(&mut fm)
-        )?;
-
         Ok(DotPrompt {
             name: name.to_string(),
             frontmatter: parsed_frontmatter,
             template,
+            partials: HashMap::new()
         })
     }
-}
 
-impl DotPrompt {
+    /// Parses a prompt, resolving the chain of prompts it extends. `load`
+    /// returns the content of a prompt file given its name.
+    pub fn try_from_extended<E: From<ParseError>>(
+        name: &str,
+        promptdata: &str,
+        load: impl Fn(&str) -> Result<String, E>
+    ) -> Result<Self, E> {
+        let (frontmatter, template) = resolve_extends(name, promptdata, &load, &mut Vec::new())?;
+
+        Ok(DotPrompt::from_parts(name, frontmatter, template)?)
+    }
+
+    pub fn with_partials(mut self, partials: HashMap<String, String>) -> Self {
+        self.partials = partials;
+        self
+    }
+
+    /// Whether the template, its partials or the system prompt mention `name`.
+    pub fn uses_variable(&self, name: &str) -> bool {
+        let system = self.frontmatter.config.as_ref().and_then(|config| config.system.as_ref());
+        std::iter::once(&self.template).chain(system).chain(self.partials.values())
+            .any(|source| source.contains(name))
+    }
+
     pub fn output_to_extract_structured_json(&self, name: &str) -> String {
-        let output_schema = &self.frontmatter.output.schema;
-        let mut properties: HashMap<String, serde_json::Value> = HashMap::new();
-        let mut required: Vec<String> = Vec::new();
-
-        for element in output_schema.values() {
-            let json_data_type: &str = if element.data_type == "number" {
-                "number"
-            } else if element.data_type == "boolean" {
-                "boolean"
-            } else if element.data_type == "integer" {
-                "integer"
-            } else {
-                "string"
-            };
-            let json_value = json!({
-                "type": json_data_type,
-                "description": element.description
-            });
-            properties.insert(element.key.clone(), json_value);
-
-            if element.required {
-                required.push(element.key.clone());
-            }
-        }
         let result2 = json!({
             "name": name,
-            "schema": {
+            "schema": self.output_json_schema()
+        });
+
+        result2.to_string()
+    }
+
+    /// JSON Schema describing the output schema given in the frontmatter.
+    pub fn output_json_schema(&self) -> serde_json::Value {
+        fn object_schema(schema: &IndexMap<String, SchemaElement>) -> serde_json::Value {
+            let mut properties: serde_json::Map<String, serde_json::Value> = serde_json::Map::new();
+            let mut required: Vec<String> = Vec::new();
+
+            for element in schema.values() {
+                properties.insert(element.key.clone(), element_schema(element));
+
+                if element.required {
+                    required.push(element.key.clone());
+                }
+            }
+
+            json!({
                 "type": "object",
                 "properties": properties,
                 "required": required
+            })
+        }
+
+        fn item_schema(element: &SchemaElement) -> serde_json::Value {
+            if element.data_type == "object" {
+                object_schema(&element.properties)
+            } else if element.data_type == "enum" {
+                json!({
+                    "type": "string",
+                    "enum": element.choices
+                })
+            } else {
+                let json_data_type: &str = if element.data_type == "number" {
+                    "number"
+                } else if element.data_type == "boolean" {
+                    "boolean"
+                } else if element.data_type == "integer" {
+                    "integer"
+                } else if element.data_type == "json" {
+                    "object"
+                } else {
+                    "string"
+                };
+                let mut json_value = json!({
+                    "type": json_data_type
+                });
+                let constraints = &element.constraints;
+                if let Some(min) = constraints.min {
+                    json_value["minimum"] = json!(min);
+                }
+                if let Some(max) = constraints.max {
+                    json_value["maximum"] = json!(max);
+                }
+                if let Some(min_length) = constraints.min_length {
+                    json_value["minLength"] = json!(min_length);
+                }
+                if let Some(max_length) = constraints.max_length {
+                    json_value["maxLength"] = json!(max_length);
+                }
+                if let Some(pattern) = constraints.pattern.as_ref() {
+                    json_value["pattern"] = json!(pattern);
+                }
+                json_value
             }
-        });
+        }
 
-        result2.to_string()
+        fn element_schema(element: &SchemaElement) -> serde_json::Value {
+            let mut json_value = if element.list {
+                json!({
+                    "type": "array",
+                    "items": item_schema(element)
+                })
+            } else {
+                item_schema(element)
+            };
+            json_value["description"] = json!(element.description);
+            json_value
+        }
+
+        object_schema(&self.frontmatter.output.schema)
     }
 }
 
@@ -640,6 +1129,349 @@ Multiple lines supported."#;
         assert!(dotprompt.template.contains("Multiple lines"));
     }
 
+    #[test]
+    fn test_input_list_and_json_types() {
+        let content = r#"---
+input:
+  schema:
+    tags: string[], Tags to apply
+    hosts?: [string, Hosts to check, comma separated]
+    ports: [integer]
+    config?: json, Extra configuration
+    name: string
+---
+Template"#;
+
+        let dotprompt = DotPrompt::try_from(content).unwrap();
+        let schema = &dotprompt.frontmatter.input.schema;
+
+        let tags = schema.get("tags").unwrap();
+        assert_eq!(tags.data_type, "string");
+        assert_eq!(tags.description, "Tags to apply");
+        assert!(tags.list);
+
+        let hosts = schema.get("hosts").unwrap();
+        assert_eq!(hosts.data_type, "string");
+        assert_eq!(hosts.description, "Hosts to check, comma separated");
+        assert!(hosts.list);
+        assert!(!hosts.required);
+
+        let ports = schema.get("ports").unwrap();
+        assert_eq!(ports.data_type, "integer");
+        assert!(ports.list);
+
+        let config = schema.get("config").unwrap();
+        assert_eq!(config.data_type, "json");
+        assert!(!config.list);
+
+        assert!(!schema.get("name").unwrap().list);
+    }
+
+    #[test]
+    fn test_input_default_values() {
+        let content = r#"---
+input:
+  schema:
+    words?: integer = 50, Summary length
+    quoted?: string = "a, b", Quoted default
+    nodesc?: number=0.5
+    plain?: string, No default = here
+    style(enum = casual, Writing style)?: [formal, casual]
+---
+Template"#;
+
+        let dotprompt = DotPrompt::try_from(content).unwrap();
+        let schema = &dotprompt.frontmatter.input.schema;
+
+        let words = schema.get("words").unwrap();
+        assert_eq!(words.data_type, "integer");
+        assert_eq!(words.default, Some("50".to_string()));
+        assert_eq!(words.description, "Summary length");
+
+        let quoted = schema.get("quoted").unwrap();
+        assert_eq!(quoted.default, Some("a, b".to_string()));
+        assert_eq!(quoted.description, "Quoted default");
+
+        let nodesc = schema.get("nodesc").unwrap();
+        assert_eq!(nodesc.data_type, "number");
+        assert_eq!(nodesc.default, Some("0.5".to_string()));
+        assert_eq!(nodesc.description, "");
+
+        let plain = schema.get("plain").unwrap();
+        assert_eq!(plain.default, None);
+        assert_eq!(plain.description, "No default = here");
+
+        let style = schema.get("style").unwrap();
+        assert_eq!(style.data_type, "enum");
+        assert_eq!(style.default, Some("casual".to_string()));
+        assert_eq!(style.description, "Writing style");
+    }
+
+    #[test]
+    fn test_input_invalid_default_values() {
+        let invalid_enum = r#"---
+input:
+  schema:
+    style(enum = bold)?: [formal, casual]
+---
+Template"#;
+        assert!(matches!(DotPrompt::try_from(invalid_enum), Err(ParseError::ParseSchemaError { .. })));
+
+        let boolean = r#"---
+input:
+  schema:
+    verbose?: boolean = true
+---
+Template"#;
+        assert!(matches!(DotPrompt::try_from(boolean), Err(ParseError::ParseSchemaError { .. })));
+    }
+
+    #[test]
+    fn test_schema_declared_order() {
+        let content = r#"---
+input:
+  schema:
+    zeta: string
+    alpha?: integer
+    mid!: string
+output:
+  format: json
+  schema:
+    summary: string
+    answer: string
+    confidence: number
+---
+Template"#;
+
+        let dotprompt = DotPrompt::try_from(content).unwrap();
+        let input_keys = dotprompt.frontmatter.input.schema.keys().collect::<Vec<_>>();
+        assert_eq!(input_keys, vec!["zeta", "alpha", "mid"]);
+
+        let structured: serde_json::Value = serde_json::from_str(
+            &dotprompt.output_to_extract_structured_json("test")).unwrap();
+        let properties = structured["schema"]["properties"].as_object().unwrap()
+            .keys().collect::<Vec<_>>();
+        assert_eq!(properties, vec!["summary", "answer", "confidence"]);
+    }
+
+    #[test]
+    fn test_nested_output_schema() {
+        let content = r#"---
+output:
+  format: json
+  schema:
+    summary: string, Short summary
+    items:
+      - name: string, Name of the finding
+        severity(enum, How severe): [low, medium, high]
+        lines?: integer[]
+      - Findings
+    meta?:
+      reviewer: string
+      score: number
+    labels: [string]
+---
+Template"#;
+
+        let dotprompt = DotPrompt::try_from(content).unwrap();
+        let items = dotprompt.frontmatter.output.schema.get("items").unwrap();
+        assert_eq!(items.data_type, "object");
+        assert!(items.list);
+        assert_eq!(items.description, "Findings");
+        assert_eq!(items.properties.keys().collect::<Vec<_>>(), vec!["name", "severity", "lines"]);
+
+        assert_eq!(dotprompt.output_json_schema(), json!({
+            "type": "object",
+            "properties": {
+                "summary": { "type": "string", "description": "Short summary" },
+                "items": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "name": { "type": "string", "description": "Name of the finding" },
+                            "severity": {
+                                "type": "string",
+                                "enum": ["low", "medium", "high"],
+                                "description": "How severe"
+                            },
+                            "lines": {
+                                "type": "array",
+                                "items": { "type": "integer" },
+                                "description": ""
+                            }
+                        },
+                        "required": ["name", "severity"]
+                    },
+                    "description": "Findings"
+                },
+                "meta": {
+                    "type": "object",
+                    "properties": {
+                        "reviewer": { "type": "string", "description": "" },
+                        "score": { "type": "number", "description": "" }
+                    },
+                    "required": ["reviewer", "score"],
+                    "description": ""
+                },
+                "labels": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": ""
+                }
+            },
+            "required": ["summary", "items", "labels"]
+        }));
+    }
+
+    #[test]
+    fn test_cli_options() {
+        let dotprompt = DotPrompt::try_from(r#"---
+input:
+  schema:
+    lang?: string
+  cli:
+    lang:
+      short: l
+      env: REVIEW_LANG
+---
+Template"#).unwrap();
+        assert_eq!(dotprompt.frontmatter.input.schema.get("lang").unwrap().cli, CliOptions {
+            short: Some('l'),
+            aliases: Vec::new(),
+            env: Some("REVIEW_LANG".to_string())
+        });
+
+        let invalid = [
+            "input:\n  schema:\n    lang: string\n  cli:\n    language:\n      short: l",
+            "input:\n  schema:\n    lang: string\n  cli:\n    lang:\n      short: h",
+            "input:\n  schema:\n    lang!: string\n  cli:\n    lang:\n      short: l",
+            "input:\n  schema:\n    a: string\n    b: string\n  cli:\n    a:\n      short: x\n    b:\n      short: x",
+            "input:\n  schema:\n    lang: string\n  cli:\n    lang:\n      long: language",
+            "input:\n  schema:\n    lang: string\n  cli:\n    lang:\n      aliases: [dry]",
+            "input:\n  schema:\n    interactive: boolean",
+            "input:\n  schema:\n    no_input: boolean",
+            "input:\n  schema:\n    lang: string\n  cli:\n    lang:\n      aliases: [config-temp]",
+            "input:\n  schema:\n    lang: string\n    language: string\n  cli:\n    lang:\n      aliases: [language]",
+            "input:\n  schema:\n    a: string\n    b: string\n  cli:\n    a:\n      aliases: [x]\n    b:\n      aliases: [x]",
+        ];
+        for frontmatter in invalid {
+            assert!(DotPrompt::try_from(format!("---\n{frontmatter}\n---\nTemplate").as_str()).is_err(), "{frontmatter}");
+        }
+    }
+
+    #[test]
+    fn test_metadata() {
+        let dotprompt = DotPrompt::try_from(r#"---
+description: Summarize a document
+version: 1.2
+author: Docs Team
+tags: [writing, docs]
+examples:
+  - summarize notes.txt
+  - summarize --words 20 notes.txt
+---
+Template"#).unwrap();
+
+        assert_eq!(dotprompt.frontmatter.metadata, Metadata {
+            description: Some("Summarize a document".to_string()),
+            version: Some("1.2".to_string()),
+            author: Some("Docs Team".to_string()),
+            tags: vec!["writing".to_string(), "docs".to_string()],
+            examples: vec!["summarize notes.txt".to_string(), "summarize --words 20 notes.txt".to_string()],
+        });
+
+        let dotprompt = DotPrompt::try_from("Template").unwrap();
+        assert_eq!(dotprompt.frontmatter.metadata, Metadata::default());
+        assert!(!dotprompt.frontmatter.interactive);
+    }
+
+    #[test]
+    fn test_interactive() {
+        let dotprompt = DotPrompt::try_from("---\ninteractive: true\n---\nTemplate").unwrap();
+        assert!(dotprompt.frontmatter.interactive);
+
+        assert!(DotPrompt::try_from("---\ninteractive: maybe\n---\nTemplate").is_err());
+    }
+
+    #[test]
+    fn test_output_retries() {
+        let dotprompt = DotPrompt::try_from(r#"---
+output:
+  format: json
+  retries: 4
+---
+Template"#).unwrap();
+        assert_eq!(dotprompt.frontmatter.output.retries, Some(4));
+
+        let dotprompt = DotPrompt::try_from("Template").unwrap();
+        assert_eq!(dotprompt.frontmatter.output.retries, None);
+    }
+
+    #[test]
+    fn test_nested_object_non_string_keys() {
+        let content = r#"---
+output:
+  format: json
+  schema:
+    meta:
+      1: string
+---
+Template"#;
+
+        assert!(matches!(DotPrompt::try_from(content), Err(ParseError::ParseSchemaError { .. })));
+    }
+
+    #[test]
+    fn test_nested_input_object() {
+        let content = r#"---
+input:
+  schema:
+    author:
+      name: string
+---
+Template"#;
+
+        let error = DotPrompt::try_from(content).unwrap_err();
+        assert_eq!(error.to_string(), "Error parsing schema: Input 'author' can not be an object, use the json type instead");
+    }
+
+    #[test]
+    fn test_invalid_positional_order() {
+        let list_not_last = r#"---
+input:
+  schema:
+    files!: string[]
+    target!: string
+---
+Template"#;
+        assert!(matches!(DotPrompt::try_from(list_not_last), Err(ParseError::ParseSchemaError { .. })));
+
+        let required_after_optional = r#"---
+input:
+  schema:
+    source?!: string
+    target!: string
+---
+Template"#;
+        assert!(matches!(DotPrompt::try_from(required_after_optional), Err(ParseError::ParseSchemaError { .. })));
+    }
+
+    #[test]
+    fn test_input_list_without_type() {
+        let content = r#"---
+input:
+  schema:
+    tags: []
+---
+Template"#;
+
+        assert!(matches!(
+            DotPrompt::try_from(content),
+            Err(ParseError::ParseSchemaError { .. })
+        ));
+    }
+
     #[test]
     fn test_valid_input_enum_type() {
         let content = r#"---
@@ -706,5 +1538,155 @@ Template here
         assert!(!dotprompt.frontmatter.input.schema.get("numbers").unwrap().required);
         assert!(!dotprompt.frontmatter.input.schema.get("numbers").unwrap().positional);
     }
-}
 
+    fn load_from(prompts: &HashMap<&str, &str>) -> impl Fn(&str) -> Result<String, ParseError> {
+        |name| prompts.get(name)
+            .map(|promptdata| promptdata.to_string())
+            .ok_or(ParseError::ExtendsError(format!("{name} not found")))
+    }
+
+    #[test]
+    fn test_extends() {
+        let prompts = HashMap::from([("base-reviewer", r#"---
+model: openai/gpt-4o
+input:
+  schema:
+    code: string, Code to review
+output:
+  format: json
+  schema:
+    issues: string[]
+config:
+  temperature: 0.2
+  cache_ttl: 60
+---
+{{#block "instructions"}}Review the code.{{/block}}
+{{#block "focus"}}Look for bugs.{{/block}}
+
+{{code}}"#)]);
+
+        let child = r#"---
+extends: base-reviewer
+model: anthropic/claude-sonnet-4
+config:
+  temperature: 0.7
+---
+{{#block "focus"}}Look for security issues.{{/block}}"#;
+
+        let dotprompt = DotPrompt::try_from_extended("security-reviewer", child, load_from(&prompts)).unwrap();
+
+        assert_eq!(dotprompt.name, "security-reviewer");
+        assert_eq!(dotprompt.frontmatter.extends.as_deref(), Some("base-reviewer"));
+        assert_eq!(dotprompt.frontmatter.model.as_deref(), Some("anthropic/claude-sonnet-4"));
+        let config = dotprompt.frontmatter.config.as_ref().unwrap();
+        assert_eq!(config.temperature, Some(0.7));
+        assert_eq!(config.cache_ttl, Some(60));
+        assert!(dotprompt.frontmatter.input.schema.contains_key("code"));
+        assert_eq!(dotprompt.frontmatter.output.format, OutputFormat::Json);
+        assert_eq!(dotprompt.template, r#"{{#block "instructions"}}Review the code.{{/block}}
+{{#block "focus"}}Look for security issues.{{/block}}
+
+{{code}}"#);
+    }
+
+    #[test]
+    fn test_extends_chain() {
+        let prompts = HashMap::from([
+            ("base", "{{#block \"a\"}}base a{{/block}} {{#block \"b\"}}base b{{/block}}"),
+            ("middle", "---\nextends: base\n---\n{{#block \"a\"}}middle a{{/block}}"),
+        ]);
+
+        let child = "---\nextends: middle\ninput:\n  schema:\n    topic: string\n---\n{{#block \"b\"}}child b{{/block}}";
+        let dotprompt = DotPrompt::try_from_extended("child", child, load_from(&prompts)).unwrap();
+
+        assert_eq!(dotprompt.template, "{{#block \"a\"}}middle a{{/block}} {{#block \"b\"}}child b{{/block}}");
+        assert!(dotprompt.frontmatter.input.schema.contains_key("topic"));
+        assert_eq!(dotprompt.frontmatter.extends.as_deref(), Some("middle"));
+    }
+
+    #[test]
+    fn test_extends_frontmatter_only() {
+        let prompts = HashMap::from([("base", "---\nmodel: openai/gpt-4o\n---\nHello")]);
+
+        let dotprompt = DotPrompt::try_from_extended(
+            "child", "---\nextends: base\nmodel: openai/gpt-4o-mini\n---\n", load_from(&prompts)).unwrap();
+
+        assert_eq!(dotprompt.template, "Hello");
+        assert_eq!(dotprompt.frontmatter.model.as_deref(), Some("openai/gpt-4o-mini"));
+    }
+
+    #[test]
+    fn test_extends_cycle() {
+        let prompts = HashMap::from([
+            ("a", "---\nextends: b\n---\n"),
+            ("b", "---\nextends: a\n---\n"),
+        ]);
+
+        let err = DotPrompt::try_from_extended("a", prompts["a"], load_from(&prompts)).unwrap_err();
+        assert!(matches!(&err, ParseError::ExtendsCycle(chain) if chain == "a -> b -> a"));
+    }
+
+    #[test]
+    fn test_extends_invalid_child() {
+        let prompts = HashMap::from([("base", "{{#block \"a\"}}base a{{/block}}")]);
+
+        let unknown_block = "---\nextends: base\n---\n{{#block \"z\"}}z{{/block}}";
+        assert!(matches!(DotPrompt::try_from_extended("child", unknown_block, load_from(&prompts)),
+            Err(ParseError::ExtendsError(_))));
+
+        let nested_blocks = HashMap::from([("base", "{{#block \"a\"}}a {{#block \"b\"}}b{{/block}} a{{/block}}")]);
+        let child = "---\nextends: base\n---\n{{#block \"b\"}}child b{{/block}}";
+        let error = DotPrompt::try_from_extended("child", child, load_from(&nested_blocks)).unwrap_err();
+        assert_eq!(error.to_string(), "Could not extend prompt: blocks can not be nested, as in block 'a'");
+
+        let outside_blocks = "---\nextends: base\n---\nSome text {{#block \"a\"}}a{{/block}}";
+        assert!(matches!(DotPrompt::try_from_extended("child", outside_blocks, load_from(&prompts)),
+            Err(ParseError::ExtendsError(_))));
+
+        let broken_parent = HashMap::from([("base", "---\nmodel: [\n---\nText")]);
+        let error = DotPrompt::try_from_extended("child", "---\nextends: base\n---\n", load_from(&broken_parent)).unwrap_err();
+        assert!(matches!(&error, ParseError::InParent { name, source }
+            if name == "base" && matches!(**source, ParseError::ParseFrontmatterError(_))), "{error:?}");
+
+        let missing_parent = "---\nextends: missing\n---\n";
+        assert!(DotPrompt::try_from_extended("child", missing_parent, load_from(&prompts)).is_err());
+    }
+
+    #[test]
+    fn test_delimiter_within_frontmatter_values() {
+        let content = r#"---
+description: Splits --- separated sections
+config:
+  system: |
+    Sections look like:
+    ---
+    text
+    ---
+---
+Split {{text}}"#;
+
+        let dotprompt = DotPrompt::try_from(content).unwrap();
+        assert_eq!(dotprompt.frontmatter.metadata.description.as_deref(), Some("Splits --- separated sections"));
+        assert_eq!(dotprompt.frontmatter.config.unwrap().system.unwrap(), "Sections look like:\n---\ntext\n---");
+        assert_eq!(dotprompt.template, "Split {{text}}");
+    }
+
+    #[test]
+    fn test_scan_sections() {
+        let sections = scan_sections("# comment\n\n---\nmodel: x\n---\n\nHello\n").unwrap();
+        assert_eq!(sections, PromptSections {
+            frontmatter: Some("model: x".to_string()),
+            frontmatter_line: 4,
+            template: "Hello".to_string()
+        });
+
+        assert!(matches!(scan_sections("---model: x\n---\nHello"), Err(ParseError::FrontmatterNotWellFormed)));
+        assert_eq!(scan_sections("  Hello\nthere").unwrap().template, "Hello\nthere");
+    }
+
+    #[test]
+    fn test_enum_error_message() {
+        let err = ParseError::EnumFieldNotWellFormed("sentiment".to_string(), "missing choices".to_string());
+        assert_eq!(err.to_string(), "Enum field 'sentiment' not well formed: missing choices");
+    }
+}
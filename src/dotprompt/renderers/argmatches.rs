@@ -1,11 +1,162 @@
-use clap::{value_parser, Arg, ArgMatches};
-use serde_json::{Value};
+use std::{fs, io::Read, path::{Path, PathBuf}};
 
-use crate::{dotprompt::{renderers::{RenderError}, DotPrompt}, executor::PromptInputs};
+use clap::{builder::{PossibleValuesParser, ValueParser}, value_parser, Arg, ArgMatches, Command};
+use indexmap::IndexMap;
+use serde_json::{json, Value};
+
+use crate::{config::appconfig, dotprompt::{media::MediaKind, renderers::{RenderError}, DotPrompt, SchemaElement}, executor::PromptInputs};
 
 pub struct DotPromptArgMatches<'a> {
     pub matches: ArgMatches,
-    pub dotprompt: &'a DotPrompt
+    pub dotprompt: &'a DotPrompt,
+    pub config: &'a appconfig::Inputs
+}
+
+fn parse_json(value: &str) -> Result<Value, serde_json::Error> {
+    serde_json::from_str(value)
+}
+
+fn parse_existing_file(value: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(value);
+    if path.is_file() {
+        Ok(path)
+    } else if path.exists() {
+        Err(format!("'{value}' is not a file"))
+    } else {
+        Err(format!("'{value}' does not exist"))
+    }
+}
+
+fn parse_media_file(value: &str) -> Result<PathBuf, String> {
+    let path = parse_existing_file(value)?;
+    match MediaKind::from_path(value) {
+        Some(_) => Ok(path),
+        None => Err(format!("'{value}' is not a supported image (png, jpeg, gif, webp) or PDF file"))
+    }
+}
+
+fn parse_image_file(value: &str) -> Result<PathBuf, String> {
+    let path = parse_existing_file(value)?;
+    match MediaKind::from_path(value) {
+        Some(kind) if kind.is_image() => Ok(path),
+        _ => Err(format!("'{value}' is not a supported image (png, jpeg, gif, webp) file"))
+    }
+}
+
+/// Media files are only attached when sending the prompt, templates get
+/// their path, name and MIME type.
+fn media_value(path: &Path) -> Value {
+    let name = path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let path = path.to_string_lossy();
+
+    json!({
+        "path": path,
+        "name": name,
+        "mime": MediaKind::from_path(&path).map(|kind| kind.mime_type())
+    })
+}
+
+/// Reads a file given to a `file` input into an object exposing its path,
+/// name and contents to the template.
+fn read_input_file(key: &str, path: &PathBuf, max_size: u64) -> Result<Value, RenderError> {
+    let file_error = |reason: String| RenderError::InputFileError {
+        key: key.to_string(),
+        path: path.to_string_lossy().to_string(),
+        reason
+    };
+
+    let file = fs::File::open(path).map_err(|err| file_error(err.to_string()))?;
+
+    // Read one byte past the limit to detect oversized files without
+    // loading them entirely.
+    let mut data = Vec::new();
+    file.take(max_size + 1).read_to_end(&mut data).map_err(|err| file_error(err.to_string()))?;
+
+    if data.len() as u64 > max_size {
+        return Err(file_error(format!("file exceeds the maximum size of {max_size} bytes")));
+    }
+
+    if data.contains(&0) {
+        return Err(file_error("binary files are not supported".to_string()));
+    }
+
+    let content = String::from_utf8(data)
+        .map_err(|_| file_error("binary files are not supported".to_string()))?;
+
+    let name = path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    Ok(json!({
+        "path": path.to_string_lossy(),
+        "name": name,
+        "content": content
+    }))
+}
+
+fn unsupported(ele: &SchemaElement) -> RenderError {
+    let data_type = if ele.list {
+        format!("{}[]", ele.data_type)
+    } else {
+        ele.data_type.clone()
+    };
+    RenderError::UnsupportedDataType {
+        key: ele.key.clone(),
+        data_type
+    }
+}
+
+fn value_parser_for(ele: &SchemaElement) -> Result<ValueParser, RenderError> {
+    let constraints = ele.constraints.clone();
+    if ele.data_type == "string" {
+        if constraints.is_empty() {
+            return Ok(value_parser!(String))
+        }
+        Ok(ValueParser::new(move |value: &str| -> Result<String, String> {
+            constraints.check_string(value)?;
+            Ok(value.to_string())
+        }))
+    } else if ele.data_type == "integer" {
+        if constraints.is_empty() {
+            return Ok(value_parser!(i64).into())
+        }
+        Ok(ValueParser::new(move |value: &str| -> Result<i64, String> {
+            let value = value.parse::<i64>().map_err(|err| err.to_string())?;
+            constraints.check_number(value as f64)?;
+            Ok(value)
+        }))
+    } else if ele.data_type == "number" {
+        if constraints.is_empty() {
+            return Ok(value_parser!(f32).into())
+        }
+        Ok(ValueParser::new(move |value: &str| -> Result<f32, String> {
+            let value = value.parse::<f32>().map_err(|err| err.to_string())?;
+            constraints.check_number(value as f64)?;
+            Ok(value)
+        }))
+    } else if ele.data_type == "enum" {
+        Ok(PossibleValuesParser::new(ele.choices.clone()).into())
+    } else if ele.data_type == "json" {
+        Ok(ValueParser::new(parse_json))
+    } else if ele.data_type == "file" {
+        Ok(ValueParser::new(parse_existing_file))
+    } else if ele.data_type == "image" {
+        Ok(ValueParser::new(parse_image_file))
+    } else if ele.data_type == "media" {
+        Ok(ValueParser::new(parse_media_file))
+    } else {
+        Err(unsupported(ele))
+    }
+}
+
+fn many_values<T: Clone + Send + Sync + 'static + Into<Value>>(matches: &ArgMatches, key: &str) -> Value {
+    match matches.get_many::<T>(key) {
+        Some(values) => Value::Array(values.cloned().map(Into::into).collect()),
+        None => Value::Array(Vec::new())
+    }
 }
 
 impl<'a> TryFrom<DotPromptArgMatches<'a>> for PromptInputs {
@@ -20,7 +171,33 @@ impl<'a> TryFrom<DotPromptArgMatches<'a>> for PromptInputs {
         let inputschema = &dp.frontmatter.input.schema;
 
         for ele in inputschema.values() {
-            let value = if ele.data_type == "boolean" {
+            let value = if ele.list {
+                if ele.data_type == "string" || ele.data_type == "enum" {
+                    many_values::<String>(matches, &ele.key)
+                } else if ele.data_type == "integer" {
+                    many_values::<i64>(matches, &ele.key)
+                } else if ele.data_type == "number" {
+                    many_values::<f32>(matches, &ele.key)
+                } else if ele.data_type == "json" {
+                    many_values::<Value>(matches, &ele.key)
+                } else if ele.data_type == "file" {
+                    let files = matches.get_many::<PathBuf>(&ele.key)
+                        .map(|paths| paths.collect::<Vec<_>>())
+                        .unwrap_or_default();
+                    Value::Array(
+                        files.into_iter()
+                            .map(|path| read_input_file(&ele.key, path, dp_matches.config.max_file_size))
+                            .collect::<Result<Vec<_>, _>>()?
+                    )
+                } else if ele.data_type == "image" || ele.data_type == "media" {
+                    match matches.get_many::<PathBuf>(&ele.key) {
+                        Some(paths) => Value::Array(paths.map(|path| media_value(path)).collect()),
+                        None => Value::Array(Vec::new())
+                    }
+                } else {
+                    return Err(unsupported(ele))
+                }
+            } else if ele.data_type == "boolean" {
                 match matches.get_one::<bool>(&ele.key) {
                     Some(value) => {
                        Value::Bool(*value)
@@ -35,7 +212,7 @@ impl<'a> TryFrom<DotPromptArgMatches<'a>> for PromptInputs {
                         Value::from(*value)
                     },
                     None => {
-                        Value::from(0)
+                        Value::Null
                     }
                 }
             } else if ele.data_type == "number" {
@@ -44,7 +221,7 @@ impl<'a> TryFrom<DotPromptArgMatches<'a>> for PromptInputs {
                         Value::from(*value)
                     },
                     None => {
-                        Value::from(0f32)
+                        Value::Null
                     }
                 }
             }
@@ -55,7 +232,7 @@ impl<'a> TryFrom<DotPromptArgMatches<'a>> for PromptInputs {
                             Value::from(value.cloned().collect::<Vec<_>>().join(" "))
                         },
                         None => {
-                            Value::from("")
+                            Value::Null
                         }
                     }
                 } else {
@@ -64,7 +241,7 @@ impl<'a> TryFrom<DotPromptArgMatches<'a>> for PromptInputs {
                             Value::from(value.to_string())
                         },
                         None => {
-                            Value::from("")
+                            Value::Null
                         }
                     }
                 }
@@ -74,13 +251,21 @@ impl<'a> TryFrom<DotPromptArgMatches<'a>> for PromptInputs {
                         Value::from(value.to_string())
                     },
                     None => {
-                        Value::from("")
+                        Value::Null
                     }
                 }
+            } else if ele.data_type == "json" {
+                matches.get_one::<Value>(&ele.key).cloned().unwrap_or(Value::Null)
+            } else if ele.data_type == "file" {
+                match matches.get_one::<PathBuf>(&ele.key) {
+                    Some(path) => read_input_file(&ele.key, path, dp_matches.config.max_file_size)?,
+                    None => Value::Null
+                }
+            } else if ele.data_type == "image" || ele.data_type == "media" {
+                matches.get_one::<PathBuf>(&ele.key).map(|path| media_value(path)).unwrap_or(Value::Null)
             }
             else {
-                return Err(RenderError::UnsupportedDataType {
-                    key: ele.key.clone(), data_type: ele.data_type.clone()})
+                return Err(unsupported(ele))
             };
             inputs.insert(ele.key.clone(), value);
         }
@@ -97,18 +282,55 @@ impl TryFrom<&DotPrompt> for Vec<Arg> {
         let inputschema = &dotprompt.frontmatter.input.schema;
         let mut args: Vec<Arg> = Vec::new();
 
+        // Only the last positional input may take several values.
+        let last_positional = inputschema.values()
+            .filter(|element| element.positional)
+            .map(|element| element.key.as_str())
+            .next_back();
+
         for inputschema_element in inputschema.values() {
             let mut arg =  Arg::new(inputschema_element.key.clone())
                 .help(inputschema_element.description.clone())
-                .required(inputschema_element.required);
+                .required(inputschema_element.required && inputschema_element.default.is_none());
+
+            if let Some(default) = inputschema_element.default.as_ref() {
+                arg = arg.default_value(default.clone());
+            }
+
+            let cli = &inputschema_element.cli;
+            if let Some(short) = cli.short {
+                arg = arg.short(short);
+            }
+            if !cli.aliases.is_empty() {
+                arg = arg.visible_aliases(cli.aliases.clone());
+            }
+            if let Some(env) = cli.env.as_ref() {
+                arg = arg.env(env.clone());
+            }
 
-            arg = if inputschema_element.data_type == "boolean" {
+            arg = if inputschema_element.list {
+                let arg = arg.value_parser(value_parser_for(inputschema_element)?);
+                if inputschema_element.positional {
+                    if inputschema_element.required {
+                        arg.num_args(1..)
+                    } else {
+                        arg.num_args(0..)
+                    }
+                } else {
+                    // Repeatable flag, e.g., --tag a --tag b
+                    arg.long(inputschema_element.key.clone())
+                        .action(clap::ArgAction::Append)
+                }
+            } else if inputschema_element.data_type == "boolean" {
                 arg.long(inputschema_element.key.clone())
                     .action(clap::ArgAction::SetTrue)
                     .required(false)
             } else if inputschema_element.data_type == "string" {
+                let arg = arg.value_parser(value_parser_for(inputschema_element)?);
                 if inputschema_element.positional {
-                        if inputschema_element.required {
+                        if last_positional != Some(inputschema_element.key.as_str()) {
+                            arg.num_args(1)
+                        } else if inputschema_element.required {
                             arg.num_args(1..)
                         } else {
                             arg.num_args(0..)
@@ -116,26 +338,471 @@ impl TryFrom<&DotPrompt> for Vec<Arg> {
                 } else {
                     arg.long(inputschema_element.key.clone())
                 }
-            } else if inputschema_element.data_type == "integer" {
+            } else {
                 arg.long(inputschema_element.key.clone())
-                    .value_parser(value_parser!(i64))
-            } else if inputschema_element.data_type == "number" {
-                arg.long(inputschema_element.key.clone())
-                    .value_parser(value_parser!(f32))
-            } else if inputschema_element.data_type == "enum" {
-                arg.long(inputschema_element.key.clone())
-                    .value_parser(inputschema_element.choices.clone())
-            }
-            else {
-                return Err(
-                    RenderError::UnsupportedDataType {
-                            key: inputschema_element.key.clone(),
-                            data_type: inputschema_element.data_type.clone()
-                        }
-                    );
+                    .value_parser(value_parser_for(inputschema_element)?)
             };
             args.push(arg);
         }
         Ok(args)
     }
 }
+
+// First line of a clap error, e.g. "invalid value 'x' for '--words <words>'"
+pub(crate) fn clap_error_message(err: &clap::Error) -> String {
+    let message = err.render().to_string();
+    let first_line = message.lines().next().unwrap_or_default();
+    first_line.strip_prefix("error: ").unwrap_or(first_line).to_string()
+}
+
+fn scalar_arg(key: &str, value: &Value) -> Result<String, RenderError> {
+    match value {
+        Value::String(value) => Ok(value.clone()),
+        Value::Number(value) => Ok(value.to_string()),
+        Value::Bool(value) => Ok(value.to_string()),
+        _ => Err(RenderError::InvalidInput(format!("value of input '{key}' must be a scalar")))
+    }
+}
+
+/// Command line arguments giving the inputs of a prompt as values, e.g.
+/// from a test case or a parent prompt. Null values are not given.
+pub fn input_args(dotprompt: &DotPrompt, inputs: &IndexMap<String, Value>) -> Result<Vec<String>, RenderError> {
+    let schema = &dotprompt.frontmatter.input.schema;
+
+    if let Some(unknown) = inputs.keys().find(|key| !schema.contains_key(*key)) {
+        return Err(RenderError::InvalidInput(format!("unknown input '{unknown}'")));
+    }
+
+    let mut named: Vec<String> = Vec::new();
+    let mut positional: Vec<String> = Vec::new();
+
+    for element in schema.values() {
+        let Some(value) = inputs.get(&element.key).filter(|value| !value.is_null()) else {
+            continue;
+        };
+
+        if element.data_type == "boolean" {
+            if value.as_bool() == Some(true) {
+                named.push(format!("--{}", element.key));
+            }
+            continue;
+        }
+
+        let items = match value {
+            Value::Array(items) if element.list => items.iter().collect(),
+            value => vec![value]
+        };
+        let values = items.into_iter()
+            .map(|item| if element.data_type == "json" {
+                serde_json::to_string(item).map_err(|err| RenderError::InvalidInput(err.to_string()))
+            } else {
+                scalar_arg(&element.key, item)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if element.positional {
+            positional.extend(values);
+        } else {
+            named.extend(values.into_iter().map(|value| format!("--{}={value}", element.key)));
+        }
+    }
+
+    Ok([named, vec!["--".to_string()], positional].concat())
+}
+
+/// Inputs of a prompt given as values, checked and converted as if they
+/// were given on its command line.
+pub fn inputs_from_values(
+    dotprompt: &DotPrompt,
+    inputs: &IndexMap<String, Value>,
+    config: &appconfig::Inputs
+) -> Result<PromptInputs, RenderError> {
+    let mut command = Command::new(dotprompt.name.clone());
+    for arg in Vec::<Arg>::try_from(dotprompt)? {
+        command = command.arg(arg);
+    }
+
+    let params = [vec![dotprompt.name.clone()], input_args(dotprompt, inputs)?].concat();
+    let matches = command.try_get_matches_from(params)
+        .map_err(|err| RenderError::InvalidInput(clap_error_message(&err)))?;
+
+    PromptInputs::try_from(DotPromptArgMatches {
+        matches,
+        dotprompt,
+        config
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Command;
+    use serde_json::json;
+
+    use super::*;
+
+    fn inputs_from(promptdata: &str, args: &[&str]) -> Result<PromptInputs, clap::Error> {
+        inputs_with_config(promptdata, args, &appconfig::Inputs::default())
+            .map(|inputs| inputs.unwrap())
+    }
+
+    fn inputs_with_config(promptdata: &str, args: &[&str], config: &appconfig::Inputs) -> Result<Result<PromptInputs, RenderError>, clap::Error> {
+        let dotprompt = DotPrompt::try_from(promptdata).unwrap();
+        let mut command = Command::new("test");
+        for arg in Vec::<Arg>::try_from(&dotprompt).unwrap() {
+            command = command.arg(arg);
+        }
+        let matches = command.try_get_matches_from([&["test"], args].concat())?;
+
+        Ok(PromptInputs::try_from(DotPromptArgMatches {
+            matches,
+            dotprompt: &dotprompt,
+            config
+        }))
+    }
+
+    #[test]
+    fn test_list_inputs() {
+        let inputs = inputs_from(r#"---
+input:
+  schema:
+    tags: string[], Tags to apply
+    ports?: [integer, Ports to check]
+    files!: [string]
+---
+{{#each files}}{{this}}{{/each}}"#,
+            &["--tags", "a", "--tags", "b", "--ports", "80", "--ports", "443", "x.txt", "y.txt"]).unwrap();
+
+        assert_eq!(inputs.map.get("tags"), Some(&json!(["a", "b"])));
+        assert_eq!(inputs.map.get("ports"), Some(&json!([80, 443])));
+        assert_eq!(inputs.map.get("files"), Some(&json!(["x.txt", "y.txt"])));
+    }
+
+    #[test]
+    fn test_list_input_omitted() {
+        let inputs = inputs_from(r#"---
+input:
+  schema:
+    tags?: string[]
+---
+Template"#, &[]).unwrap();
+
+        assert_eq!(inputs.map.get("tags"), Some(&json!([])));
+    }
+
+    #[test]
+    fn test_list_input_invalid_item() {
+        assert!(inputs_from(r#"---
+input:
+  schema:
+    ports: integer[]
+---
+Template"#, &["--ports", "eighty"]).is_err());
+    }
+
+    #[test]
+    fn test_json_input() {
+        let inputs = inputs_from(r#"---
+input:
+  schema:
+    config: json, Configuration object
+---
+Template"#, &["--config", r#"{"hosts": ["a", "b"], "retries": 3}"#]).unwrap();
+
+        assert_eq!(inputs.map.get("config"), Some(&json!({"hosts": ["a", "b"], "retries": 3})));
+    }
+
+    #[test]
+    fn test_json_input_invalid() {
+        assert!(inputs_from(r#"---
+input:
+  schema:
+    config: json
+---
+Template"#, &["--config", "{not json"]).is_err());
+    }
+
+    #[test]
+    fn test_file_input() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("review.md");
+        fs::write(&path, "# Title\nBody").unwrap();
+        let path = path.to_string_lossy().to_string();
+
+        let inputs = inputs_from(r#"---
+input:
+  schema:
+    doc: file, Document to review
+    extra?: file
+---
+{{doc.content}}"#, &["--doc", &path]).unwrap();
+
+        assert_eq!(inputs.map.get("doc"), Some(&json!({
+            "path": path,
+            "name": "review.md",
+            "content": "# Title\nBody"
+        })));
+        assert_eq!(inputs.map.get("extra"), Some(&Value::Null));
+    }
+
+    #[test]
+    fn test_media_inputs() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let image = dir.path().join("shot.png");
+        let doc = dir.path().join("spec.pdf");
+        fs::write(&image, [0x89, b'P', b'N', b'G']).unwrap();
+        fs::write(&doc, "%PDF-1.4").unwrap();
+        let image = image.to_string_lossy().to_string();
+        let doc = doc.to_string_lossy().to_string();
+
+        let promptdata = r#"---
+input:
+  schema:
+    screenshot: image, Screenshot to triage
+    docs?: media[]
+---
+{{media screenshot}}"#;
+
+        let inputs = inputs_from(promptdata, &["--screenshot", &image, "--docs", &doc]).unwrap();
+        assert_eq!(inputs.map.get("screenshot"), Some(&json!({
+            "path": image,
+            "name": "shot.png",
+            "mime": "image/png"
+        })));
+        assert_eq!(inputs.map.get("docs").unwrap()[0]["mime"], json!("application/pdf"));
+
+        // PDFs are not images
+        assert!(inputs_from(promptdata, &["--screenshot", &doc]).is_err());
+    }
+
+    #[test]
+    fn test_file_input_positional_list() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let first = dir.path().join("a.txt");
+        let second = dir.path().join("b.txt");
+        fs::write(&first, "A").unwrap();
+        fs::write(&second, "B").unwrap();
+
+        let inputs = inputs_from(r#"---
+input:
+  schema:
+    files!: file[]
+---
+{{#each files}}{{this.content}}{{/each}}"#,
+            &[&first.to_string_lossy(), &second.to_string_lossy()]).unwrap();
+
+        let contents = inputs.map.get("files").unwrap().as_array().unwrap().iter()
+            .map(|file| file["content"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(contents, vec!["A", "B"]);
+    }
+
+    #[test]
+    fn test_file_input_missing() {
+        assert!(inputs_from(r#"---
+input:
+  schema:
+    doc: file
+---
+Template"#, &["--doc", "/does/not/exist.txt"]).is_err());
+    }
+
+    #[test]
+    fn test_file_input_too_large() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("big.txt");
+        fs::write(&path, "0123456789").unwrap();
+
+        let result = inputs_with_config(r#"---
+input:
+  schema:
+    doc: file
+---
+Template"#, &["--doc", &path.to_string_lossy()], &appconfig::Inputs { max_file_size: 5, ..Default::default() }).unwrap();
+
+        assert!(matches!(result, Err(RenderError::InputFileError { .. })));
+    }
+
+    #[test]
+    fn test_file_input_binary() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("image.png");
+        fs::write(&path, [0x89, 0x50, 0x4e, 0x47, 0x00, 0x1a]).unwrap();
+
+        let result = inputs_with_config(r#"---
+input:
+  schema:
+    doc: file
+---
+Template"#, &["--doc", &path.to_string_lossy()], &appconfig::Inputs::default()).unwrap();
+
+        assert!(matches!(result, Err(RenderError::InputFileError { .. })));
+    }
+
+    #[test]
+    fn test_constraints() {
+        let promptdata = r#"---
+input:
+  schema:
+    words?: integer(min=1, max=500) = 50, Summary length
+    temperature?: number(min=0, max=1)
+    slug?: string(pattern="^[a-z-]+$", max_length=10)
+---
+Template"#;
+
+        let inputs = inputs_from(promptdata, &["--words", "20", "--slug", "rust-cli"]).unwrap();
+        assert_eq!(inputs.map.get("words"), Some(&json!(20)));
+        assert_eq!(inputs.map.get("slug"), Some(&json!("rust-cli")));
+
+        let err = inputs_from(promptdata, &["--words=-5"]).err().unwrap();
+        assert!(err.to_string().contains("must be at least 1"));
+        assert!(inputs_from(promptdata, &["--words", "501"]).is_err());
+        assert!(inputs_from(promptdata, &["--temperature", "1.5"]).is_err());
+        assert!(inputs_from(promptdata, &["--slug", "Rust"]).is_err());
+        assert!(inputs_from(promptdata, &["--slug", "much-too-long"]).is_err());
+    }
+
+    #[test]
+    fn test_default_values() {
+        let promptdata = r#"---
+input:
+  schema:
+    words?: integer = 50, Summary length
+    lang?: string = "en, de", Languages
+    style(enum = formal, Writing style)?: [formal, casual]
+    topic?: string, Topic
+    count?: integer
+---
+Template"#;
+        let inputs = inputs_from(promptdata, &[]).unwrap();
+
+        assert_eq!(inputs.map.get("words"), Some(&json!(50)));
+        assert_eq!(inputs.map.get("lang"), Some(&json!("en, de")));
+        assert_eq!(inputs.map.get("style"), Some(&json!("formal")));
+        assert_eq!(inputs.map.get("topic"), Some(&Value::Null));
+        assert_eq!(inputs.map.get("count"), Some(&Value::Null));
+
+        let inputs = inputs_from(promptdata, &["--words", "0", "--style", "casual"]).unwrap();
+        assert_eq!(inputs.map.get("words"), Some(&json!(0)));
+        assert_eq!(inputs.map.get("style"), Some(&json!("casual")));
+    }
+
+    #[test]
+    fn test_required_with_default() {
+        let inputs = inputs_from(r#"---
+input:
+  schema:
+    words: integer = 50
+---
+Template"#, &[]).unwrap();
+
+        assert_eq!(inputs.map.get("words"), Some(&json!(50)));
+    }
+
+    #[test]
+    fn test_default_shown_in_help() {
+        let dotprompt = DotPrompt::try_from(r#"---
+input:
+  schema:
+    words?: integer = 50, Summary length
+---
+Template"#).unwrap();
+        let mut command = Command::new("test");
+        for arg in Vec::<Arg>::try_from(&dotprompt).unwrap() {
+            command = command.arg(arg);
+        }
+        let help = command.render_help().to_string();
+        assert!(help.contains("[default: 50]"), "{help}");
+    }
+
+    #[test]
+    fn test_unset_optionals_render_as_missing() {
+        use std::collections::HashMap;
+        use crate::dotprompt::renderers::Render;
+
+        let promptdata = r#"---
+input:
+  schema:
+    words?: integer, Summary length
+    text?: string
+---
+Summarize{{#if words}} in {{words}} words{{/if}}: {{text}}"#;
+        let dotprompt = DotPrompt::try_from(promptdata).unwrap();
+        let inputs = inputs_from(promptdata, &[]).unwrap();
+
+        assert_eq!(dotprompt.render(inputs, HashMap::new()).unwrap(), "Summarize: ");
+    }
+
+    #[test]
+    fn test_multiple_positionals_in_declared_order() {
+        let promptdata = r#"---
+input:
+  schema:
+    source!: string, Source
+    target!: string, Target
+    rest?!: string, Remaining words
+---
+Template"#;
+        let inputs = inputs_from(promptdata, &["a.txt", "b.txt", "c", "d"]).unwrap();
+
+        assert_eq!(inputs.map.get("source"), Some(&json!("a.txt")));
+        assert_eq!(inputs.map.get("target"), Some(&json!("b.txt")));
+        assert_eq!(inputs.map.get("rest"), Some(&json!("c d")));
+    }
+
+    #[test]
+    fn test_help_in_declared_order() {
+        let dotprompt = DotPrompt::try_from(r#"---
+input:
+  schema:
+    zeta: string, Zeta
+    alpha: string, Alpha
+    mid: integer, Mid
+---
+Template"#).unwrap();
+        let args = Vec::<Arg>::try_from(&dotprompt).unwrap();
+        let keys = args.iter().map(|arg| arg.get_id().as_str()).collect::<Vec<_>>();
+
+        assert_eq!(keys, vec!["zeta", "alpha", "mid"]);
+    }
+
+    #[test]
+    fn test_cli_options() {
+        let promptdata = r#"---
+input:
+  schema:
+    lang?: string, Review language
+    strict?: boolean
+    files!: string[]
+  cli:
+    lang:
+      short: l
+      aliases: [language]
+      env: PROMPTCMD_TEST_REVIEW_LANG
+    strict:
+      short: s
+---
+Template"#;
+
+        let inputs = inputs_from(promptdata, &["-l", "rust", "-s", "main.rs"]).unwrap();
+        assert_eq!(inputs.map.get("lang"), Some(&json!("rust")));
+        assert_eq!(inputs.map.get("strict"), Some(&json!(true)));
+
+        let inputs = inputs_from(promptdata, &["--language", "go", "main.go"]).unwrap();
+        assert_eq!(inputs.map.get("lang"), Some(&json!("go")));
+
+        temp_env::with_var("PROMPTCMD_TEST_REVIEW_LANG", Some("python"), || {
+            let inputs = inputs_from(promptdata, &["main.py"]).unwrap();
+            assert_eq!(inputs.map.get("lang"), Some(&json!("python")));
+        });
+
+        let dotprompt = DotPrompt::try_from(promptdata).unwrap();
+        let mut command = Command::new("test");
+        for arg in Vec::<Arg>::try_from(&dotprompt).unwrap() {
+            command = command.arg(arg);
+        }
+        let help = command.render_help().to_string();
+        assert!(help.contains("-l, --lang <lang>"));
+        assert!(help.contains("[env: PROMPTCMD_TEST_REVIEW_LANG=]"));
+        assert!(help.contains("[aliases: --language]"));
+    }
+}
@@ -0,0 +1,171 @@
+use std::ffi::OsString;
+
+use clap::{ArgMatches, Command};
+
+use crate::dotprompt::helpers::Terminal;
+use crate::dotprompt::renderers::argmatches::clap_error_message;
+use crate::dotprompt::renderers::RenderError;
+use crate::dotprompt::{DotPrompt, SchemaElement};
+
+// Required inputs are checked once answers are given
+fn relaxed(command: &Command) -> Command {
+    command.clone().mut_args(|arg| arg.required(false))
+}
+
+/// Matches of the command line arguments `params` ignoring missing
+/// required inputs, `None` if they are invalid otherwise.
+pub fn relaxed_matches(command: &Command, params: &[OsString]) -> Option<ArgMatches> {
+    relaxed(command).try_get_matches_from(params).ok()
+}
+
+fn question(promptname: &str, element: &SchemaElement) -> String {
+    let label = match element.description.is_empty() {
+        true => &element.key,
+        false => &element.description
+    };
+    let hint = match element.choices.is_empty() {
+        true => element.data_type.clone(),
+        false => element.choices.join("/")
+    };
+
+    if element.list {
+        format!("{promptname}> {label} ({hint}, comma-separated): ")
+    } else {
+        format!("{promptname}> {label} ({hint}): ")
+    }
+}
+
+/// Command line arguments `params` with the answer given for an input.
+fn with_answer(params: &[OsString], element: &SchemaElement, answer: &str) -> Vec<OsString> {
+    let values: Vec<&str> = if element.list {
+        answer.split(',').map(|value| value.trim()).filter(|value| !value.is_empty()).collect()
+    } else {
+        vec![answer]
+    };
+
+    let mut params = params.to_vec();
+    if element.positional {
+        // Values after -- are never taken for flags
+        if !params.iter().skip(1).any(|param| param == "--") {
+            params.push("--".into());
+        }
+        params.extend(values.iter().map(OsString::from));
+    } else {
+        let flags = values.iter().map(|value| format!("--{}={value}", element.key).into());
+        params.splice(1..1, flags);
+    }
+    params
+}
+
+/// Asks on the terminal for the required inputs missing from the command
+/// line arguments `params`, returning them completed with the answers.
+pub fn ask_missing_inputs(
+    command: &Command,
+    dotprompt: &DotPrompt,
+    matches: &ArgMatches,
+    mut params: Vec<OsString>,
+    terminal: &dyn Terminal
+) -> Result<Vec<OsString>, RenderError> {
+    let command = relaxed(command);
+
+    let missing = dotprompt.frontmatter.input.schema.values()
+        .filter(|element| element.required && element.default.is_none() && element.data_type != "boolean")
+        .filter(|element| matches.value_source(&element.key).is_none());
+
+    for element in missing {
+        let prompt_error = |reason: String| RenderError::InputPromptError {
+            key: element.key.clone(),
+            reason
+        };
+
+        loop {
+            let answer = terminal.prompt(&question(&dotprompt.name, element), false, false)
+                .map_err(|err| prompt_error(err.to_string()))?
+                .ok_or(prompt_error("no answer given".to_string()))?;
+
+            let answer = answer.trim();
+            if answer.is_empty() {
+                terminal.message("A value is required").map_err(|err| prompt_error(err.to_string()))?;
+                continue;
+            }
+
+            let candidate = with_answer(&params, element, answer);
+            match command.clone().try_get_matches_from(&candidate) {
+                Ok(_) => {
+                    params = candidate;
+                    break;
+                }
+                Err(err) => terminal.message(&clap_error_message(&err)).map_err(|err| prompt_error(err.to_string()))?
+            }
+        }
+    }
+
+    Ok(params)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{Arg, Command};
+
+    use super::*;
+    use crate::dotprompt::helpers::ScriptedTerminal;
+
+    const REVIEW: &str = r#"---
+input:
+  schema:
+    file!: string, File to review
+    focus!: string[]
+    tone(enum): [formal, casual]
+    words: integer(min=10)
+    strict?: boolean
+---
+{{file}}"#;
+
+    fn complete(promptdata: &str, args: &[&str], answers: &[&'static str]) -> (Result<Vec<OsString>, String>, Vec<String>) {
+        let dotprompt = DotPrompt::try_from(("review", promptdata)).unwrap();
+        let mut command = Command::new("review");
+        for arg in Vec::<Arg>::try_from(&dotprompt).unwrap() {
+            command = command.arg(arg);
+        }
+
+        let params = [&["review"], args].concat().iter().map(OsString::from).collect::<Vec<_>>();
+        let matches = relaxed_matches(&command, &params).unwrap();
+        let terminal = ScriptedTerminal::new(answers);
+
+        let result = ask_missing_inputs(&command, &dotprompt, &matches, params, &terminal)
+            .map_err(|err| err.to_string());
+        let shown = terminal.shown.lock().unwrap().clone();
+        (result, shown)
+    }
+
+    #[test]
+    fn test_ask_missing_inputs() {
+        let (params, shown) = complete(REVIEW, &["--tone", "casual"], &["main.rs", "", "bugs, style", "5", "20"]);
+
+        assert_eq!(params.unwrap(), vec!["review", "--words=20", "--tone", "casual", "--", "main.rs", "bugs", "style"]);
+        assert_eq!(shown, vec![
+            "review> File to review (string): ",
+            "review> focus (string, comma-separated): ",
+            "A value is required",
+            "review> focus (string, comma-separated): ",
+            "review> words (integer): ",
+            "invalid value '5' for '--words <words>': must be at least 10",
+            "review> words (integer): ",
+        ]);
+    }
+
+    #[test]
+    fn test_enum_choices() {
+        let (params, shown) = complete(REVIEW, &["main.rs", "bugs", "--words", "50"], &["rude", "formal"]);
+
+        assert_eq!(params.unwrap(), vec!["review", "--tone=formal", "main.rs", "bugs", "--words", "50"]);
+        assert_eq!(shown[0], "review> tone (formal/casual): ");
+        assert!(shown[1].starts_with("invalid value 'rude' for '--tone <tone>'"));
+    }
+
+    #[test]
+    fn test_no_answer() {
+        let (result, _) = complete(REVIEW, &[], &[]);
+        assert_eq!(result.unwrap_err(), "Could not ask for input file: no answer given");
+    }
+}
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 pub mod keyvalue;
 pub mod argmatches;
+pub mod interactive;
 
 
 use thiserror::Error;
@@ -15,6 +16,22 @@ pub enum RenderError {
         data_type: String
     },
 
+    #[error("Could not read file '{path}' given for {key}: {reason}")]
+    InputFileError {
+        key: String,
+        path: String,
+        reason: String
+    },
+
+    #[error("{0}")]
+    InvalidInput(String),
+
+    #[error("Could not ask for input {key}: {reason}")]
+    InputPromptError {
+        key: String,
+        reason: String
+    },
+
     #[error("HandlebarsRenderTemplateError: {0}")]
     HandlebarsRenderTemplateError(#[from] HBRenderError),
 
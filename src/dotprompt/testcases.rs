@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+
+use indexmap::IndexMap;
+use regex::Regex;
+use serde::Deserialize;
+use serde_yaml::Value;
+
+use crate::dotprompt::{split_promptdata, ParseError};
+
+/// A test case of a prompt, given in the `tests` section of its frontmatter
+/// or in a sibling `<name>.test.yaml` file.
+#[derive(Debug, Deserialize, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct TestCase {
+    pub name: Option<String>,
+    // Input values, as they would be given on the command line
+    #[serde(default)]
+    pub inputs: IndexMap<String, Value>,
+    pub stdin: Option<String>,
+    // Output of the exec helper, by command line, e.g. "git diff --staged"
+    #[serde(default)]
+    pub exec: HashMap<String, String>,
+    // Output of the prompt helper, by prompt name
+    #[serde(default)]
+    pub prompt: HashMap<String, String>,
+    // Answers of the ask helper, by question
+    #[serde(default)]
+    pub ask: HashMap<String, String>,
+    // Assertions on the rendered prompt
+    #[serde(default)]
+    pub expect: Assertions,
+    // Stubbed model response, checked against the output schema
+    pub response: Option<String>,
+    pub expect_response: Option<Assertions>,
+}
+
+#[derive(Debug, Deserialize, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Assertions {
+    pub snapshot: Option<String>,
+    #[serde(default)]
+    pub contains: Vec<String>,
+    #[serde(default)]
+    pub not_contains: Vec<String>,
+    // Regular expressions the text must match
+    #[serde(default)]
+    pub matches: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct TestsFrontmatter {
+    #[serde(default)]
+    tests: Vec<TestCase>,
+}
+
+/// Test cases of a prompt: the ones from its frontmatter followed by the
+/// ones from its tests file. Cases of extended prompts are not inherited.
+pub fn parse_test_cases(promptdata: &str, testsdata: Option<&str>) -> Result<Vec<TestCase>, ParseError> {
+    let mut cases = match split_promptdata(promptdata)? {
+        (Some(frontmatter), _) if !frontmatter.is_null() => {
+            serde_yaml::from_value::<TestsFrontmatter>(frontmatter)?.tests
+        }
+        _ => Vec::new()
+    };
+
+    if let Some(testsdata) = testsdata {
+        cases.extend(serde_yaml::from_str::<Option<Vec<TestCase>>>(testsdata)?.unwrap_or_default());
+    }
+
+    Ok(cases)
+}
+
+impl Assertions {
+    /// Checks the assertions against the given text, returning the failed ones.
+    pub fn check(&self, what: &str, text: &str) -> Vec<String> {
+        let mut failures = Vec::new();
+
+        if let Some(snapshot) = self.snapshot.as_ref() && snapshot.trim_end() != text.trim_end() {
+            failures.push(format!("{what} does not match the snapshot:\n--- expected\n{}\n--- actual\n{}",
+                snapshot.trim_end(), text.trim_end()));
+        }
+        for expected in &self.contains {
+            if !text.contains(expected.as_str()) {
+                failures.push(format!("{what} does not contain {expected:?}"));
+            }
+        }
+        for unexpected in &self.not_contains {
+            if text.contains(unexpected.as_str()) {
+                failures.push(format!("{what} contains {unexpected:?}"));
+            }
+        }
+        for pattern in &self.matches {
+            match Regex::new(pattern) {
+                Ok(regex) if regex.is_match(text) => {}
+                Ok(_) => failures.push(format!("{what} does not match {pattern:?}")),
+                Err(err) => failures.push(format!("invalid pattern {pattern:?}: {err}")),
+            }
+        }
+
+        failures
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_test_cases() {
+        let promptdata = r#"---
+input:
+  schema:
+    words: integer
+tests:
+  - name: short
+    inputs:
+      words: 10
+    stdin: Some article
+    exec:
+      date +%Y: "2024"
+    expect:
+      contains: [10 words]
+---
+Summarize in {{words}} words"#;
+
+        let cases = parse_test_cases(promptdata, Some("- name: from file\n  response: ok")).unwrap();
+
+        assert_eq!(cases.len(), 2);
+        assert_eq!(cases[0].name.as_deref(), Some("short"));
+        assert_eq!(cases[0].inputs.get("words"), Some(&Value::from(10)));
+        assert_eq!(cases[0].exec.get("date +%Y").map(|s| s.as_str()), Some("2024"));
+        assert_eq!(cases[0].expect.contains, vec!["10 words"]);
+        assert_eq!(cases[1].response.as_deref(), Some("ok"));
+
+        assert!(parse_test_cases("Just a template", None).unwrap().is_empty());
+        assert!(parse_test_cases("---\ntests:\n  - inptus: {}\n---\nHi", None).is_err());
+    }
+
+    #[test]
+    fn test_assertions() {
+        let assertions = Assertions {
+            snapshot: Some("Hello world\n".to_string()),
+            contains: vec!["world".to_string(), "moon".to_string()],
+            not_contains: vec!["Hello".to_string()],
+            matches: vec!["^Hello".to_string(), "[".to_string()],
+        };
+
+        let failures = assertions.check("rendered prompt", "Hello world");
+        assert_eq!(failures.len(), 3);
+        assert_eq!(failures[0], "rendered prompt does not contain \"moon\"");
+        assert_eq!(failures[1], "rendered prompt contains \"Hello\"");
+        assert!(failures[2].starts_with("invalid pattern \"[\""));
+
+        assert_eq!(assertions.check("response", "Bye").len(), 5);
+    }
+}
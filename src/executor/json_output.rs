@@ -0,0 +1,315 @@
+use regex::Regex;
+use serde_json::Value;
+
+use crate::executor::extract_fenced_code;
+
+pub const DEFAULT_JSON_RETRIES: u32 = 2;
+
+/// Parses a model response as JSON, repairing it first if needed, and
+/// validates it against the given JSON schema. On success the (possibly
+/// repaired) JSON text is returned, otherwise a description of the problems
+/// found, suitable for feeding back to the model.
+pub fn parse_and_validate(response: &str, schema: Option<&Value>) -> Result<String, String> {
+    let (text, value) = parse(response)?;
+
+    if let Some(schema) = schema {
+        let mut errors = Vec::new();
+        validate(&value, schema, "$", &mut errors);
+        if !errors.is_empty() {
+            return Err(errors.join("; "));
+        }
+    }
+
+    Ok(text)
+}
+
+pub fn retry_message(reason: &str) -> String {
+    format!(
+        "Your previous response was not valid JSON for the requested schema: {reason}. \
+        Reply again with only the corrected JSON document, without any explanation or code fences."
+    )
+}
+
+fn parse(response: &str) -> Result<(String, Value), String> {
+    let mut candidates: Vec<String> = Vec::new();
+
+    let trimmed = response.trim();
+    candidates.push(trimmed.to_string());
+
+    if let Some(fenced) = extract_fenced_code(trimmed).into_iter().next() {
+        candidates.push(fenced.trim().to_string());
+    }
+
+    // Text surrounding the JSON document, e.g. "Here is the result: {...}"
+    if let Some(start) = trimmed.find(['{', '[']) {
+        let end = trimmed.rfind(['}', ']']).filter(|end| *end > start);
+        let inner = match end {
+            Some(end) => &trimmed[start..=end],
+            None => &trimmed[start..]
+        };
+        candidates.push(inner.to_string());
+    }
+
+    let mut first_error = None;
+    for candidate in candidates {
+        match serde_json::from_str::<Value>(&candidate) {
+            Ok(value) => return Ok((candidate, value)),
+            Err(err) => {
+                first_error.get_or_insert(err.to_string());
+            }
+        }
+
+        let repaired = repair(&candidate);
+        if let Ok(value) = serde_json::from_str::<Value>(&repaired) {
+            return Ok((repaired, value));
+        }
+    }
+
+    Err(format!("response is not valid JSON ({})", first_error.unwrap_or_default()))
+}
+
+/// Fixes common syntax problems: trailing commas, and unterminated strings,
+/// arrays or objects as left behind by truncated responses.
+fn repair(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut closers: Vec<char> = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for c in text.chars() {
+        if in_string {
+            out.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+
+        match c {
+            '"' => in_string = true,
+            '{' => closers.push('}'),
+            '[' => closers.push(']'),
+            '}' | ']' => {
+                trim_trailing_comma(&mut out);
+                closers.pop();
+            }
+            _ => {}
+        }
+        out.push(c);
+    }
+
+    if in_string {
+        if escaped {
+            out.pop();
+        }
+        out.push('"');
+    }
+    while let Some(closer) = closers.pop() {
+        trim_trailing_comma(&mut out);
+        out.push(closer);
+    }
+
+    out
+}
+
+fn trim_trailing_comma(out: &mut String) {
+    let trimmed_len = out.trim_end().len();
+    if out[..trimmed_len].ends_with(',') {
+        out.truncate(trimmed_len - 1);
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn matches_type(value: &Value, expected: &str) -> bool {
+    match expected {
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64()
+            || value.as_f64().is_some_and(|number| number.fract() == 0.0),
+        "boolean" => value.is_boolean(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        "null" => value.is_null(),
+        _ => true
+    }
+}
+
+/// Validates the subset of JSON schema produced by `DotPrompt::output_json_schema`:
+/// type, enum, properties, required, items and value constraints.
+fn validate(value: &Value, schema: &Value, path: &str, errors: &mut Vec<String>) {
+    if let Some(expected) = schema.get("type").and_then(|t| t.as_str())
+        && !matches_type(value, expected) {
+        errors.push(format!("{path}: expected {expected}, found {}", type_name(value)));
+        return;
+    }
+
+    if let Some(choices) = schema.get("enum").and_then(|e| e.as_array())
+        && !choices.contains(value) {
+        let choices = choices.iter().map(|choice| choice.to_string()).collect::<Vec<_>>();
+        errors.push(format!("{path}: {value} is not one of {}", choices.join(", ")));
+    }
+
+    if let Some(number) = value.as_f64() {
+        if let Some(minimum) = schema.get("minimum").and_then(|m| m.as_f64()) && number < minimum {
+            errors.push(format!("{path}: {value} is less than {minimum}"));
+        }
+        if let Some(maximum) = schema.get("maximum").and_then(|m| m.as_f64()) && number > maximum {
+            errors.push(format!("{path}: {value} is greater than {maximum}"));
+        }
+    }
+
+    if let Some(text) = value.as_str() {
+        let length = text.chars().count() as u64;
+        if let Some(min_length) = schema.get("minLength").and_then(|m| m.as_u64()) && length < min_length {
+            errors.push(format!("{path}: shorter than {min_length} characters"));
+        }
+        if let Some(max_length) = schema.get("maxLength").and_then(|m| m.as_u64()) && length > max_length {
+            errors.push(format!("{path}: longer than {max_length} characters"));
+        }
+        if let Some(pattern) = schema.get("pattern").and_then(|p| p.as_str())
+            && let Ok(regex) = Regex::new(pattern)
+            && !regex.is_match(text) {
+            errors.push(format!("{path}: does not match the pattern {pattern}"));
+        }
+    }
+
+    if let Some(object) = value.as_object() {
+        if let Some(required) = schema.get("required").and_then(|r| r.as_array()) {
+            for key in required.iter().filter_map(|key| key.as_str()) {
+                if !object.contains_key(key) {
+                    errors.push(format!("{path}: missing required property '{key}'"));
+                }
+            }
+        }
+
+        if let Some(properties) = schema.get("properties").and_then(|p| p.as_object()) {
+            for (key, property_schema) in properties {
+                if let Some(property) = object.get(key) {
+                    validate(property, property_schema, &format!("{path}.{key}"), errors);
+                }
+            }
+        }
+    }
+
+    if let Some(items) = value.as_array()
+        && let Some(item_schema) = schema.get("items") {
+        for (idx, item) in items.iter().enumerate() {
+            validate(item, item_schema, &format!("{path}[{idx}]"), errors);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::dotprompt::DotPrompt;
+
+    fn schema() -> Value {
+        DotPrompt::try_from(r#"---
+output:
+  format: json
+  schema:
+    title: string, The title
+    rating: integer
+    tags: string[]
+    sentiment(enum): [positive, negative]
+    author:
+      name: string
+---
+Review"#).unwrap().output_json_schema()
+    }
+
+    #[test]
+    fn test_valid_json() {
+        let response = r#"{"title": "Dune", "rating": 5, "tags": ["scifi"], "sentiment": "positive", "author": {"name": "Frank"}}"#;
+        assert_eq!(parse_and_validate(response, Some(&schema())), Ok(response.to_string()));
+    }
+
+    #[test]
+    fn test_fenced_json() {
+        let response = "Sure, here you go:\n```json\n{\"title\": \"Dune\", \"rating\": 5, \"tags\": [], \"sentiment\": \"positive\", \"author\": {\"name\": \"Frank\"}}\n```\n";
+        let json = parse_and_validate(response, Some(&schema())).unwrap();
+        assert!(json.starts_with('{') && json.ends_with('}'));
+    }
+
+    #[test]
+    fn test_surrounding_text() {
+        let response = r#"The answer is {"a": 1}. Hope that helps!"#;
+        assert_eq!(parse_and_validate(response, None), Ok(r#"{"a": 1}"#.to_string()));
+    }
+
+    #[test]
+    fn test_repair_trailing_commas_and_truncation() {
+        assert_eq!(repair(r#"{"a": [1, 2,], "b": "x",}"#), r#"{"a": [1, 2], "b": "x"}"#);
+        assert_eq!(repair(r#"{"a": [1, 2"#), r#"{"a": [1, 2]}"#);
+        assert_eq!(repair(r#"{"a": "tru"#), r#"{"a": "tru"}"#);
+        assert_eq!(repair(r#"{"a": "x,}", "b": 1,"#), r#"{"a": "x,}", "b": 1}"#);
+    }
+
+    #[test]
+    fn test_not_json() {
+        let err = parse_and_validate("I can not help with that", None).unwrap_err();
+        assert!(err.starts_with("response is not valid JSON"));
+    }
+
+    #[test]
+    fn test_schema_violations() {
+        let response = json!({
+            "title": 3,
+            "rating": 4.5,
+            "tags": ["ok", 1],
+            "sentiment": "neutral",
+            "author": {}
+        }).to_string();
+
+        let err = parse_and_validate(&response, Some(&schema())).unwrap_err();
+        assert_eq!(err, [
+            "$.title: expected string, found number",
+            "$.rating: expected integer, found number",
+            "$.tags[1]: expected string, found number",
+            "$.sentiment: \"neutral\" is not one of \"positive\", \"negative\"",
+            "$.author: missing required property 'name'",
+        ].join("; "));
+    }
+
+    #[test]
+    fn test_constraint_violations() {
+        let schema = DotPrompt::try_from(r#"---
+output:
+  format: json
+  schema:
+    score: integer(min=1, max=5)
+    code: string(pattern="^[A-Z]{3}$", max_length=3)
+---
+Rate"#).unwrap().output_json_schema();
+
+        assert!(parse_and_validate(r#"{"score": 3, "code": "ABC"}"#, Some(&schema)).is_ok());
+        assert_eq!(parse_and_validate(r#"{"score": 7, "code": "abcd"}"#, Some(&schema)).unwrap_err(), [
+            "$.score: 7 is greater than 5",
+            "$.code: longer than 3 characters",
+            "$.code: does not match the pattern ^[A-Z]{3}$",
+        ].join("; "));
+    }
+
+    #[test]
+    fn test_missing_required() {
+        let err = parse_and_validate(r#"{"title": "Dune"}"#, Some(&schema())).unwrap_err();
+        assert!(err.contains("$: missing required property 'rating'"));
+    }
+}
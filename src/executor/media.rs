@@ -0,0 +1,182 @@
+use std::{collections::HashMap, fs, io::Read};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use llm::chat::{ChatMessage, ChatMessageBuilder, ImageMime};
+
+use crate::{dotprompt::{media::MediaKind, messages::{MessageRole, PromptMessages}}, executor::ExecutorErorr};
+
+pub struct MediaAttachment {
+    pub kind: MediaKind,
+    pub data: Vec<u8>
+}
+
+pub fn load_media(path: &str, max_size: u64) -> Result<MediaAttachment, ExecutorErorr> {
+    let media_error = |reason: String| ExecutorErorr::MediaFileError {
+        path: path.to_string(),
+        reason
+    };
+
+    let kind = MediaKind::from_path(path)
+        .ok_or(media_error("unsupported media type".to_string()))?;
+
+    let file = fs::File::open(path).map_err(|err| media_error(err.to_string()))?;
+
+    let mut data = Vec::new();
+    file.take(max_size + 1).read_to_end(&mut data).map_err(|err| media_error(err.to_string()))?;
+
+    if data.len() as u64 > max_size {
+        return Err(media_error(format!("file exceeds the maximum size of {max_size} bytes")));
+    }
+
+    Ok(MediaAttachment { kind, data })
+}
+
+/// Fails on media within system sections, which are sent as plain text.
+pub fn check_placement(prompt_messages: &PromptMessages) -> Result<(), ExecutorErorr> {
+    let system_media = prompt_messages.messages.iter()
+        .filter(|message| message.role == MessageRole::System)
+        .find_map(|message| message.media.first());
+
+    match system_media {
+        Some(path) => Err(ExecutorErorr::MediaInSystemMessage(path.clone())),
+        None => Ok(())
+    }
+}
+
+/// Fails if the provider can not take the given media in the request.
+/// Requests to providers not known here are let through.
+pub fn check_support(provider: &str, media: &[MediaKind]) -> Result<(), ExecutorErorr> {
+    let supports = |kind: &MediaKind| match provider {
+        "anthropic" | "google" => true,
+        "openai" | "openrouter" | "ollama" => kind.is_image(),
+        _ => true
+    };
+
+    match media.iter().find(|kind| !supports(kind)) {
+        Some(kind) => Err(ExecutorErorr::UnsupportedMedia {
+            provider: provider.to_string(),
+            mime: kind.mime_type().to_string()
+        }),
+        None => Ok(())
+    }
+}
+
+fn media_message(builder: ChatMessageBuilder, provider: &str, attachment: &MediaAttachment) -> ChatMessage {
+    let builder = match attachment.kind {
+        MediaKind::Pdf => builder.pdf(attachment.data.clone()),
+        // OpenAI compatible backends only take images by URL
+        kind if matches!(provider, "openai" | "openrouter") => builder.image_url(
+            format!("data:{};base64,{}", kind.mime_type(), BASE64.encode(&attachment.data))),
+        MediaKind::Png => builder.image(ImageMime::PNG, attachment.data.clone()),
+        MediaKind::Jpeg => builder.image(ImageMime::JPEG, attachment.data.clone()),
+        MediaKind::Gif => builder.image(ImageMime::GIF, attachment.data.clone()),
+        MediaKind::Webp => builder.image(ImageMime::WEBP, attachment.data.clone()),
+    };
+    builder.build()
+}
+
+/// Whether the response to a request with the given media can be streamed.
+/// The llm crate does not implement documents in streamed requests to
+/// Anthropic (it panics), thus PDFs require a non-streamed request.
+pub fn can_stream(provider: &str, media: &[MediaKind]) -> bool {
+    provider != "anthropic" || media.iter().all(|kind| kind.is_image())
+}
+
+/// Builds the chat messages of a conversation. Each attachment is sent as a
+/// message of its own, preceding the text of the message it belongs to.
+pub fn chat_messages(
+    prompt_messages: &PromptMessages,
+    media: &HashMap<String, MediaAttachment>,
+    provider: &str
+) -> Vec<ChatMessage> {
+    let builder = |role: MessageRole| match role {
+        MessageRole::Assistant => ChatMessage::assistant(),
+        _ => ChatMessage::user()
+    };
+
+    let mut messages = Vec::new();
+    for message in prompt_messages.conversation() {
+        for path in &message.media {
+            if let Some(attachment) = media.get(path) {
+                messages.push(media_message(builder(message.role), provider, attachment));
+            }
+        }
+        if !message.content.is_empty() {
+            messages.push(builder(message.role).content(message.content.clone()).build());
+        }
+    }
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use llm::chat::MessageType;
+
+    use super::*;
+    use crate::dotprompt::messages::{media_marker, role_block_close_marker, role_marker};
+
+    fn attachments() -> HashMap<String, MediaAttachment> {
+        HashMap::from([
+            ("shot.png".to_string(), MediaAttachment { kind: MediaKind::Png, data: vec![1, 2, 3] }),
+            ("spec.pdf".to_string(), MediaAttachment { kind: MediaKind::Pdf, data: vec![4] }),
+        ])
+    }
+
+    #[test]
+    fn test_chat_messages() {
+        let rendered = format!("{}{}Explain", media_marker("shot.png"), media_marker("spec.pdf"));
+        let prompt_messages = PromptMessages::from(rendered.as_str());
+
+        let messages = chat_messages(&prompt_messages, &attachments(), "google");
+        assert_eq!(messages.len(), 3);
+        assert!(matches!(&messages[0].message_type, MessageType::Image((ImageMime::PNG, data)) if data == &vec![1, 2, 3]));
+        assert!(matches!(&messages[1].message_type, MessageType::Pdf(data) if data == &vec![4]));
+        assert!(matches!(messages[2].message_type, MessageType::Text));
+        assert_eq!(messages[2].content, "Explain");
+    }
+
+    #[test]
+    fn test_chat_messages_image_url() {
+        let rendered = format!("{}Explain", media_marker("shot.png"));
+        let prompt_messages = PromptMessages::from(rendered.as_str());
+
+        let messages = chat_messages(&prompt_messages, &attachments(), "openai");
+        assert!(matches!(&messages[0].message_type, MessageType::ImageURL(url) if url == "data:image/png;base64,AQID"));
+    }
+
+    #[test]
+    fn test_check_placement() {
+        let rendered = format!("{}{}You review designs{}Explain",
+            role_marker(MessageRole::System), media_marker("shot.png"), role_block_close_marker());
+        let error = check_placement(&PromptMessages::from(rendered.as_str())).unwrap_err();
+        assert_eq!(error.to_string(), "Media can not be attached to system messages: 'shot.png'");
+
+        let rendered = format!("{}Explain", media_marker("shot.png"));
+        assert!(check_placement(&PromptMessages::from(rendered.as_str())).is_ok());
+    }
+
+    #[test]
+    fn test_check_support() {
+        assert!(check_support("anthropic", &[MediaKind::Png, MediaKind::Pdf]).is_ok());
+        assert!(check_support("openai", &[MediaKind::Jpeg]).is_ok());
+        assert!(check_support("ollama", &[MediaKind::Pdf]).is_err());
+        assert!(check_support("google", &[MediaKind::Pdf]).is_ok());
+        assert!(check_support("deepseek", &[MediaKind::Png]).is_ok());
+
+        assert!(!can_stream("anthropic", &[MediaKind::Png, MediaKind::Pdf]));
+        assert!(can_stream("anthropic", &[MediaKind::Png]));
+        assert!(can_stream("google", &[MediaKind::Pdf]));
+    }
+
+    #[test]
+    fn test_load_media_too_large() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("big.png");
+        fs::write(&path, [0u8; 16]).unwrap();
+        let path = path.to_string_lossy();
+
+        assert!(load_media(&path, 16).is_ok());
+        assert!(matches!(load_media(&path, 8), Err(ExecutorErorr::MediaFileError { .. })));
+        assert!(load_media("notes.txt", 8).is_err());
+    }
+}
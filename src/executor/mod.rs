@@ -1,4 +1,4 @@
-use std::{collections::HashMap, io::{BufReader}, sync::{Arc, Mutex}, time::Instant};
+use std::{collections::HashMap, sync::{atomic::{AtomicU32, Ordering}, Arc}, time::Instant};
 use handlebars::HelperDef;
 use llm::{builder::LLMBuilder, chat::{ChatMessage, StructuredOutputFormat}, LLMProvider};
 use log::debug;
@@ -20,7 +20,7 @@ use crate::{
             Resolver}
     },
     dotprompt::{
-        helpers, DotPrompt, OutputFormat
+        helpers, messages::PromptMessages, DotPrompt, OutputFormat
     },
     executor::{
         partiallog::{ExecutionLogData, PartialLogRecord}, streaming_output::StreamingExecutionOutput, structured_streaming_output::StructuredStreamingExecutionOutput
@@ -34,6 +34,8 @@ use crate::dotprompt::renderers::Render;
 use crate::lb;
 use crate::stats::store;
 use crate::storage;
+pub(crate) mod json_output;
+mod media;
 mod partiallog;
 mod streaming_output;
 mod structured_streaming_output;
@@ -53,6 +55,12 @@ pub struct PromptInputs {
     pub map: HashMap<String, Value>,
 }
 
+impl Default for PromptInputs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl PromptInputs {
     pub fn new() -> Self {
         Self {
@@ -90,15 +98,113 @@ pub enum ExecutorErorr {
     #[error("JSON Error: {0}")]
     JSONError(#[from] serde_json::Error),
 
+    #[error("Could not read media file '{path}': {reason}")]
+    MediaFileError {
+        path: String,
+        reason: String
+    },
+
+    #[error("Media can not be attached to system messages: '{0}'")]
+    MediaInSystemMessage(String),
+
+    #[error("Provider {provider} does not support {mime} attachments")]
+    UnsupportedMedia {
+        provider: String,
+        mime: String
+    },
+
+    #[error("Invalid JSON output after {attempts} attempt(s): {reason}")]
+    InvalidJSONOutput {
+        attempts: u32,
+        reason: String
+    },
+
+    #[error("Nested prompt cycle: {}", .0.join(" -> "))]
+    PromptCycle(Vec<String>),
+
+    #[error("Nested prompts exceed the maximum depth of {max_depth}: {}", .chain.join(" -> "))]
+    MaxDepthExceeded {
+        max_depth: usize,
+        chain: Vec<String>
+    },
+
+    #[error("Reached the maximum of {0} LLM calls per invocation")]
+    MaxLLMCallsExceeded(u32),
+
     #[error("{0}")]
     Other(String),
 }
 
+/// Where in a run a prompt is executed: the prompts enclosing it, its node
+/// in the run's trace, and the LLM calls made so far by the run.
+#[derive(Clone)]
+pub struct CallChain {
+    pub prompts: Vec<String>,
+    pub llm_calls: Arc<AtomicU32>,
+    pub run_id: String,
+    pub node: u32,
+    pub parent: Option<u32>,
+    // Order in which the parent started this prompt
+    pub position: Option<u32>,
+    // Executions started in the run, numbering their nodes
+    pub nodes: Arc<AtomicU32>,
+    // Prompts nested in this one started so far
+    pub children: Arc<AtomicU32>,
+}
+
+impl Default for CallChain {
+    /// The chain of a new run
+    fn default() -> Self {
+        let seed = format!("{:?}|{}", std::time::SystemTime::now(), std::process::id());
+        CallChain {
+            prompts: Vec::new(),
+            llm_calls: Arc::default(),
+            run_id: format!("{:012x}", xxh3_64(seed.as_bytes()) & 0xffff_ffff_ffff),
+            node: 0,
+            parent: None,
+            position: None,
+            nodes: Arc::new(AtomicU32::new(1)),
+            children: Arc::default()
+        }
+    }
+}
+
 pub struct Executor {
     pub loadbalancer: lb::WeightedLoadBalancer,
     pub appconfig: &'static appconfig::AppConfig,
     pub statsstore: &'static dyn store::StatsStore,
     pub prompts_storage: &'static dyn storage::PromptFilesStorage,
+    // Shared with nested prompts, read once
+    pub stdin: Arc<helpers::SharedStdin>,
+    // Where ask questions are answered, None when input is disabled
+    pub terminal: Option<Arc<dyn helpers::Terminal>>,
+    pub chain: CallChain,
+}
+
+/// Executor over the given prompts with the default config, keeping its
+/// stats in a temporary directory.
+#[cfg(test)]
+pub(crate) fn test_executor(storage: storage::promptfiles_mem::InMemoryPromptFilesStorage) -> Arc<Executor> {
+    test_executor_with(storage, appconfig::AppConfig::default())
+}
+
+#[cfg(test)]
+pub(crate) fn test_executor_with(storage: storage::promptfiles_mem::InMemoryPromptFilesStorage,
+    appconfig: appconfig::AppConfig) -> Arc<Executor> {
+    use crate::stats::rusqlite_store::RusqliteStore;
+
+    let statsdir = Box::leak(Box::new(tempfile::TempDir::new().unwrap()));
+    let statsstore: &'static RusqliteStore = Box::leak(Box::new(RusqliteStore::new(statsdir.path().to_path_buf()).unwrap()));
+
+    Arc::new(Executor {
+        loadbalancer: lb::WeightedLoadBalancer { stats: statsstore },
+        appconfig: Box::leak(Box::new(appconfig)),
+        statsstore,
+        prompts_storage: Box::leak(Box::new(storage)),
+        stdin: Arc::new(helpers::SharedStdin::new(std::io::empty(), appconfig::DEFAULT_MAX_STDIN_SIZE)),
+        terminal: None,
+        chain: CallChain::default()
+    })
 }
 
 pub(crate) fn extract_fenced_code(input: &str) -> Vec<String> {
@@ -121,10 +227,59 @@ impl Executor {
 
         debug!("Promptfile path: {path}");
 
-        let dotprompt = dotprompt::DotPrompt::try_from((promptname, promptfile_content.as_str()))?;
+        let dotprompt = dotprompt::DotPrompt::try_from_extended(promptname, promptfile_content.as_str(),
+            |parent| Ok::<_, ExecutorErorr>(self.prompts_storage.load(parent)?.1))?
+            .with_partials(self.prompts_storage.partials()?);
 
         Ok(dotprompt)
     }
+    /// Executor of the prompt `promptname` nested in `parent`, failing on
+    /// cycles and beyond the maximum depth.
+    pub fn nested(&self, parent: &str, promptname: &str) -> Result<Executor, ExecutorErorr> {
+        let mut prompts = self.chain.prompts.clone();
+        prompts.push(parent.to_string());
+
+        if let Some(start) = prompts.iter().position(|name| name == promptname) {
+            let mut cycle = prompts.split_off(start);
+            cycle.push(promptname.to_string());
+            return Err(ExecutorErorr::PromptCycle(cycle));
+        }
+
+        let max_depth = self.appconfig.nested.max_depth;
+        if prompts.len() > max_depth {
+            prompts.push(promptname.to_string());
+            return Err(ExecutorErorr::MaxDepthExceeded { max_depth, chain: prompts });
+        }
+
+        Ok(Executor {
+            loadbalancer: lb::WeightedLoadBalancer { stats: self.loadbalancer.stats },
+            appconfig: self.appconfig,
+            statsstore: self.statsstore,
+            prompts_storage: self.prompts_storage,
+            stdin: self.stdin.clone(),
+            terminal: self.terminal.clone(),
+            chain: CallChain {
+                prompts,
+                llm_calls: self.chain.llm_calls.clone(),
+                run_id: self.chain.run_id.clone(),
+                node: self.chain.nodes.fetch_add(1, Ordering::SeqCst),
+                parent: Some(self.chain.node),
+                position: Some(self.chain.children.fetch_add(1, Ordering::SeqCst) + 1),
+                nodes: self.chain.nodes.clone(),
+                children: Arc::default()
+            }
+        })
+    }
+
+    // Counts a request to the provider against the invocation's maximum
+    fn count_llm_call(&self) -> Result<(), ExecutorErorr> {
+        let max_llm_calls = self.appconfig.nested.max_llm_calls;
+        if self.chain.llm_calls.fetch_add(1, Ordering::SeqCst) >= max_llm_calls {
+            return Err(ExecutorErorr::MaxLLMCallsExceeded(max_llm_calls));
+        }
+        Ok(())
+    }
+
     fn cache_key(
         promptname: &str,
         provider: &str,
@@ -144,6 +299,8 @@ impl Executor {
         xxh3_64(full_data.as_bytes()) as i64
     }
 
+    /// Executes the prompt, recording it in the run's trace unless it is
+    /// only rendered.
     pub async fn execute_dotprompt(
         self: Arc<Self>,
         dotprompt: &dotprompt::DotPrompt,
@@ -153,26 +310,82 @@ impl Executor {
         dry: bool,
         render_only: bool) -> Result<ExecutionOutput, ExecutorErorr>{
 
+        let traced = !dry && !render_only;
+        if traced {
+            if self.chain.parent.is_none() {
+                debug!("Run id: {}", self.chain.run_id);
+            }
+
+            let execution = store::ExecutionRecord {
+                run_id: self.chain.run_id.clone(),
+                node: self.chain.node,
+                parent: self.chain.parent,
+                depth: self.chain.prompts.len() as u32,
+                position: self.chain.position,
+                promptname: dotprompt.name.clone(),
+                error: None,
+                created: chrono::Utc::now()
+            };
+            if let Err(err) = self.statsstore.log_execution(execution) {
+                error!("Logging execution failed: {}", err);
+            }
+        }
+
+        let result = self.clone().run_dotprompt(dotprompt, overrides, requested_model, inputs, dry, render_only).await;
+
+        if traced && let Err(err) = &result
+            && let Err(err) = self.statsstore.log_execution_error(&self.chain.run_id, self.chain.node, &err.to_string()) {
+            error!("Logging execution failed: {}", err);
+        }
+
+        result
+    }
+
+    async fn run_dotprompt(
+        self: Arc<Self>,
+        dotprompt: &dotprompt::DotPrompt,
+        overrides: Option<ResolvedGlobalProperties>,
+        requested_model: Option<String>,
+        mut inputs: PromptInputs,
+        dry: bool,
+        render_only: bool) -> Result<ExecutionOutput, ExecutorErorr>{
+
         debug!("Executing dotprompt");
 
         let next_exec = self.clone();
-        let prompt_helper: Box<dyn HelperDef + Send + Sync> = Box::new(helpers::PromptHelper {
+        let prefetched = Arc::new(helpers::Prefetched::default());
+        let prompt_helper = helpers::PromptHelper {
             executor: next_exec,
-            dry, render_only
-        });
+            promptname: dotprompt.name.clone(),
+            dry, render_only,
+            prefetched: prefetched.clone()
+        };
 
-        let exec_helper: Box<dyn HelperDef + Send + Sync> = Box::new(helpers::ExecHelper);
+        self.stdin.insert_has_stdin(dotprompt, &mut inputs);
+
+        // Independent nested calls run concurrently ahead of rendering. Dry
+        // runs of nested prompts print their output, thus run in order.
+        if !dry {
+            prefetched.run(dotprompt, &inputs.map, &prompt_helper, self.appconfig.nested.max_parallel).await;
+        }
+
+        let prompt_helper: Box<dyn HelperDef + Send + Sync> = Box::new(prompt_helper);
+        let exec_helper: Box<dyn HelperDef + Send + Sync> = Box::new(helpers::ExecHelper {
+            prefetched
+        });
         let concat_helper: Box<dyn HelperDef + Send + Sync> = Box::new(helpers::ConcatHelper);
         let stdin_helper: Box<dyn HelperDef + Send + Sync> = Box::new(helpers::StdinHelper {
-            inp: Mutex::new(BufReader::new(std::io::stdin()))
+            stdin: self.stdin.clone()
         });
         let stdin_helper2: Box<dyn HelperDef + Send + Sync> = Box::new(helpers::StdinHelper {
-            inp: Mutex::new(BufReader::new(std::io::stdin()))
+            stdin: self.stdin.clone()
         });
         let ask_helper: Box<dyn HelperDef + Send + Sync> = Box::new(helpers::AskHelper {
             promptname: dotprompt.name.clone(),
-            inp: Mutex::new(BufReader::new(std::io::stdin()))
+            terminal: self.terminal.clone()
         });
+        let role_helper: Box<dyn HelperDef + Send + Sync> = Box::new(helpers::RoleHelper);
+        let media_helper: Box<dyn HelperDef + Send + Sync> = Box::new(helpers::MediaHelper);
 
         let helpers_map: HashMap<&str, Box<dyn HelperDef + Send + Sync>> = HashMap::from([
             ("exec", exec_helper),
@@ -181,9 +394,13 @@ impl Executor {
             ("stdin", stdin_helper),
             ("STDIN", stdin_helper2),
             ("ask", ask_helper),
+            ("role", role_helper),
+            ("media", media_helper),
         ]);
 
         let rendered_dotprompt: String = dotprompt.render(inputs, helpers_map)?;
+        let prompt_messages = PromptMessages::from(rendered_dotprompt.as_str());
+        let rendered_dotprompt = prompt_messages.to_string();
 
         debug!("{rendered_dotprompt}");
 
@@ -191,10 +408,20 @@ impl Executor {
             return Ok(ExecutionOutput::RenderOnly(rendered_dotprompt));
         }
 
+        media::check_placement(&prompt_messages)?;
+
+        // The rendered system sections (from the frontmatter's config.system and
+        // role sections in the template) replace the raw frontmatter system
+        // prompt, and are still overridable by inputs.
+        let mut fm_global_properties = GlobalProviderProperties::from(&dotprompt.frontmatter);
+        if let Some(system) = prompt_messages.system() {
+            fm_global_properties.system = Some(system);
+        }
+
         let resolver = Resolver {
             overrides,
             fm_properties: Some(ResolvedGlobalProperties::from((
-                &GlobalProviderProperties::from(&dotprompt.frontmatter),
+                &fm_global_properties,
                 ResolvedPropertySource::Dotprompt(dotprompt.name.clone()),
             ))
         )};
@@ -239,6 +466,21 @@ impl Executor {
             llmbuilder = llmbuilder.schema(output_schema);
         }
 
+        // Without a declared schema any JSON document is accepted
+        let output_schema = (!dotprompt.frontmatter.output.schema.is_empty())
+            .then(|| dotprompt.output_json_schema());
+
+        let mut stream = globals.stream.as_ref().is_some_and(|stream| stream.value);
+
+        let media_kinds = prompt_messages.media()
+            .filter_map(dotprompt::media::MediaKind::from_path)
+            .collect::<Vec<_>>();
+        media::check_support(&model_info.provider, &media_kinds)?;
+        if stream && !media::can_stream(&model_info.provider, &media_kinds) {
+            warn!("{} does not support streaming with documents attached, defaulting to non-stream", model_info.provider);
+            stream = false;
+        }
+
         if dry {
             println!("Dry run mode");
             println!("=============");
@@ -258,28 +500,72 @@ impl Executor {
             return Ok(ExecutionOutput::DryRun)
         }
 
+        let mut media: HashMap<String, media::MediaAttachment> = HashMap::new();
+        let mut cache_data = rendered_dotprompt.clone();
+        for path in prompt_messages.media() {
+            if !media.contains_key(path) {
+                let attachment = media::load_media(path, self.appconfig.inputs.max_media_size)?;
+                // Attachments are part of the cache key by content
+                cache_data.push_str(&format!("|{:x}", xxh3_64(&attachment.data)));
+                media.insert(path.to_string(), attachment);
+            }
+        }
+
         let cache_key = Executor::cache_key(
             &dotprompt.template,
             &model_info.provider,
             &model_info.model,
             variant_name.as_deref(),
             group_choice.as_ref().map(|(n, _)| n.as_str()),
-            &rendered_dotprompt
+            &cache_data
         );
 
+        let partial_log_record = PartialLogRecord {
+            statsstore: self.statsstore,
+            promptname: dotprompt.name.clone(),
+            provider: model_info.provider.clone(),
+            model: model_info.model.clone(),
+            variant: variant_name.clone(),
+            group: group_choice.map(|(n, _)| n.clone()),
+            cache_key: Some(cache_key),
+            run_id: self.chain.run_id.clone(),
+            node: self.chain.node
+        };
+
         if let Some(cache_ttl) = &globals.cache_ttl && cache_ttl.value > 0 {
             debug!("Cache requested, ttl set to {} seconds via {}", cache_ttl.value, &cache_ttl.source);
             match self.statsstore.cached(cache_key, cache_ttl.value) {
                 Ok(Some(record)) => {
                     debug!("Found cached response");
 
-                    if matches!(dotprompt.frontmatter.output.format, OutputFormat::Code) {
-                        let fenced_codes = extract_fenced_code(record.result.as_str());
-                        if !fenced_codes.is_empty() {
-                            return Ok(ExecutionOutput::Cached(fenced_codes.join("\n")));
+                    let output = match dotprompt.frontmatter.output.format {
+                        OutputFormat::Code => {
+                            let fenced_codes = extract_fenced_code(record.result.as_str());
+                            if !fenced_codes.is_empty() {
+                                Some(fenced_codes.join("\n"))
+                            } else {
+                                Some(record.result.clone())
+                            }
                         }
+                        // Cached responses that do not pass validation are ignored
+                        OutputFormat::Json => {
+                            match json_output::parse_and_validate(&record.result, output_schema.as_ref()) {
+                                Ok(json) => Some(json),
+                                Err(err) => {
+                                    debug!("Ignoring invalid cached response: {err}");
+                                    None
+                                }
+                            }
+                        }
+                        _ => Some(record.result.clone())
+                    };
+
+                    if let Some(output) = output {
+                        if let Err(err) = partial_log_record.log_cache_hit(&record.result) {
+                            error!("Logging execution failed: {}", err);
+                        }
+                        return Ok(ExecutionOutput::Cached(output));
                     }
-                    return Ok(ExecutionOutput::Cached(record.result))
                 },
                 Ok(None) => {
                     debug!("No cache found")
@@ -292,34 +578,24 @@ impl Executor {
 
         let llm = llmbuilder.build()?;
 
-        let messages = vec![
-            ChatMessage::user()
-                .content(rendered_dotprompt)
-                .build(),
-        ];
+        let messages = media::chat_messages(&prompt_messages, &media, &model_info.provider);
 
-        let start_time = Instant::now();
+        if messages.is_empty() {
+            return Err(ExecutorErorr::Other("Rendered prompt contains no user or assistant messages".to_string()));
+        }
 
-        let partial_log_record = PartialLogRecord {
-            statsstore: self.statsstore,
-            promptname: dotprompt.template.clone(),
-            provider: model_info.provider.clone(),
-            model: model_info.model.clone(),
-            variant: variant_name.clone(),
-            group: group_choice.map(|(n, _)| n.clone()),
-            cache_key: Some(cache_key)
-        };
+        let start_time = Instant::now();
 
-        async fn exec_immediate(
-            llm: Box<dyn LLMProvider>, messages: Vec<ChatMessage>, start_time: Instant, partial_log_record: PartialLogRecord,
-            dotprompt: &DotPrompt
-        ) -> Result<ExecutionOutput, ExecutorErorr> {
-            let result = llm.chat(&messages).await;
+        // Sends the chat request and logs it, returning the response text
+        async fn exec_chat(
+            llm: &dyn LLMProvider, messages: &[ChatMessage], start_time: Instant, partial_log_record: &PartialLogRecord
+        ) -> Result<String, ExecutorErorr> {
+            let result = llm.chat(messages).await;
 
             let elapsed = start_time.elapsed().as_secs() as u32;
 
             // Send chat request and handle the response
-            let (success, response_text, prompt_tokens, completion_tokens) = match result  {
+            let (success, response_text, prompt_tokens, completion_tokens) = match &result  {
                 Ok(response) => {
 
                     let response_text = response.text().unwrap_or_default();
@@ -345,6 +621,22 @@ impl Executor {
                 error!("Logging execution failed: {}", err);
             }
 
+            result?;
+            Ok(response_text)
+        }
+
+        async fn exec_immediate(
+            llm: &dyn LLMProvider, messages: &[ChatMessage], start_time: Instant, partial_log_record: &PartialLogRecord,
+            dotprompt: &DotPrompt
+        ) -> Result<ExecutionOutput, ExecutorErorr> {
+            let response_text = match exec_chat(llm, messages, start_time, partial_log_record).await {
+                Ok(response_text) => response_text,
+                Err(err) => match err {
+                    ExecutorErorr::LLMError(err) => err.to_string(),
+                    err => return Err(err)
+                }
+            };
+
             if matches!(dotprompt.frontmatter.output.format, OutputFormat::Code) {
                 let fenced_codes = extract_fenced_code(response_text.as_str());
                 if !fenced_codes.is_empty() {
@@ -354,7 +646,61 @@ impl Executor {
             Ok(ExecutionOutput::ImmediateOutput(response_text))
         }
 
-        if let Some(stream)= globals.stream.as_ref() && stream.value {
+        // Runs the request to completion, streaming it if requested, and returns
+        // the full response text. Every call is logged.
+        async fn exec_collect(
+            llm: &dyn LLMProvider, messages: &[ChatMessage], stream: bool, provider: &str,
+            partial_log_record: &PartialLogRecord, dotprompt: &DotPrompt
+        ) -> Result<String, ExecutorErorr> {
+            if !stream || provider == "ollama" {
+                return exec_chat(llm, messages, Instant::now(), partial_log_record).await;
+            }
+
+            let output_format = dotprompt.frontmatter.output.format.clone();
+            match provider {
+                "openai" | "google" | "openrouter" => {
+                    let stream = llm.chat_stream_struct(messages).await?;
+                    StructuredStreamingExecutionOutput::new(partial_log_record.clone(), stream, output_format)
+                        .sync_collect().await
+                }
+                _ => {
+                    let stream = llm.chat_stream(messages).await?;
+                    StreamingExecutionOutput::new(partial_log_record.clone(), stream, output_format)
+                        .sync_collect().await
+                }
+            }
+        }
+
+        // JSON responses are only returned once they pass validation. Invalid
+        // ones are sent back along with the validation errors for correction.
+        if matches!(dotprompt.frontmatter.output.format, OutputFormat::Json) {
+            if stream {
+                warn!("JSON output is validated before it is printed, so it is not streamed");
+            }
+            let retries = dotprompt.frontmatter.output.retries.unwrap_or(json_output::DEFAULT_JSON_RETRIES);
+            let mut messages = messages;
+            let mut attempt = 0;
+            loop {
+                attempt += 1;
+                self.count_llm_call()?;
+                let response_text = exec_collect(
+                    llm.as_ref(), &messages, stream, &model_info.provider, &partial_log_record, dotprompt).await?;
+
+                match json_output::parse_and_validate(&response_text, output_schema.as_ref()) {
+                    Ok(json) => return Ok(ExecutionOutput::ImmediateOutput(json)),
+                    Err(reason) if attempt <= retries => {
+                        warn!("Invalid JSON output (attempt {attempt}): {reason}");
+                        messages.push(ChatMessage::assistant().content(response_text).build());
+                        messages.push(ChatMessage::user().content(json_output::retry_message(&reason)).build());
+                    }
+                    Err(reason) => return Err(ExecutorErorr::InvalidJSONOutput { attempts: attempt, reason })
+                }
+            }
+        }
+
+        self.count_llm_call()?;
+
+        if stream {
             debug!("stream mode");
 
             match model_info.provider.as_str() {
@@ -376,7 +722,7 @@ impl Executor {
                 },
                 "ollama" => {
                     warn!("Ollama provider currently does not support streaming, defaulting to non-stream");
-                    exec_immediate(llm, messages, start_time, partial_log_record, dotprompt).await
+                    exec_immediate(llm.as_ref(), &messages, start_time, &partial_log_record, dotprompt).await
                 }
                 _ => {
                     match llm.chat_stream(&messages).await {
@@ -397,7 +743,7 @@ impl Executor {
                 }
             }
         } else {
-            exec_immediate(llm, messages, start_time, partial_log_record, dotprompt).await
+            exec_immediate(llm.as_ref(), &messages, start_time, &partial_log_record, dotprompt).await
         }
     }
 
@@ -410,3 +756,31 @@ impl Executor {
         self.execute_dotprompt(&dotprompt, overrides, requested_model,inputs, dry, render_only).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_call_chain() {
+        let executor = test_executor(storage::promptfiles_mem::InMemoryPromptFilesStorage::default());
+
+        let nested = executor.nested("main", "a").unwrap().nested("a", "b").unwrap();
+        assert_eq!(nested.chain.prompts, vec!["main", "a"]);
+        assert_eq!((nested.chain.node, nested.chain.parent, nested.chain.position), (2, Some(1), Some(1)));
+        assert_eq!(nested.chain.run_id, executor.chain.run_id);
+
+        let sibling = executor.nested("main", "c").unwrap();
+        assert_eq!((sibling.chain.node, sibling.chain.parent, sibling.chain.position), (3, Some(0), Some(2)));
+
+        assert_eq!(nested.nested("b", "a").err().unwrap().to_string(), "Nested prompt cycle: a -> b -> a");
+        assert_eq!(nested.nested("b", "b").err().unwrap().to_string(), "Nested prompt cycle: b -> b");
+
+        // LLM calls are counted for the whole invocation
+        for _ in 1..appconfig::DEFAULT_MAX_LLM_CALLS {
+            nested.count_llm_call().unwrap();
+        }
+        executor.count_llm_call().unwrap();
+        assert_eq!(nested.count_llm_call().err().unwrap().to_string(), "Reached the maximum of 50 LLM calls per invocation");
+    }
+}
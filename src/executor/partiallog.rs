@@ -2,6 +2,7 @@ use chrono::Utc;
 
 use crate::stats::store::{self, LogRecord, StatsStore};
 
+#[derive(Clone)]
 pub struct PartialLogRecord {
     pub statsstore: &'static dyn StatsStore,
     pub promptname: String,
@@ -10,6 +11,8 @@ pub struct PartialLogRecord {
     pub provider: String,
     pub model: String,
     pub cache_key: Option<i64>,
+    pub run_id: String,
+    pub node: u32,
 }
 
 pub struct ExecutionLogData<'a> {
@@ -34,7 +37,31 @@ impl PartialLogRecord {
             success: execdata.success,
             time_taken: execdata.time_taken,
             created: Utc::now(),
-            cache_key: self.cache_key
+            cache_key: self.cache_key,
+            run_id: Some(self.run_id.clone()),
+            node: Some(self.node),
+            cached: false
+        })
+    }
+
+    /// Logs a response taken from the cache, which is not a request.
+    pub fn log_cache_hit(&self, result: &str) -> Result<(), store::LogError> {
+        self.statsstore.log(LogRecord {
+            promptname: self.promptname.clone(),
+            provider: self.provider.clone(),
+            model: self.model.clone(),
+            variant: self.variant.clone(),
+            group: self.group.clone(),
+            prompt_tokens: 0,
+            completion_tokens: 0,
+            result: result.to_string(),
+            success: true,
+            time_taken: 0,
+            created: Utc::now(),
+            cache_key: None,
+            run_id: Some(self.run_id.clone()),
+            node: Some(self.node),
+            cached: true
         })
     }
 }
@@ -1,11 +1,11 @@
 use std::{path::PathBuf, sync::{Arc, Mutex}};
 
 use chrono::{Duration, Utc};
-use rusqlite::{params, params_from_iter, Connection};
+use rusqlite::{params, params_from_iter, Connection, Row};
 use thiserror::Error;
 use log::debug;
 
-use crate::stats::{store::{FetchError, LogError, LogRecord, StatsStore, SummaryItem}, DB_NAME};
+use crate::stats::{store::{ExecutionRecord, FetchError, LogError, LogRecord, StatsStore, SummaryItem, TraceNode}, DB_NAME};
 
 pub struct RusqliteStore {
     conn: Arc<Mutex<Connection>>
@@ -71,7 +71,29 @@ impl RusqliteStore {
             )?;
         }
 
-        tx.pragma_update(None, "user_version", 3)?;
+        if version < 4 {
+            debug!("Applying v4 migration");
+            tx.execute_batch(
+                "ALTER TABLE logs ADD COLUMN run_id TEXT;
+                ALTER TABLE logs ADD COLUMN node INTEGER;
+                ALTER TABLE logs ADD COLUMN cached INTEGER NOT NULL DEFAULT 0;
+                CREATE INDEX idx_logs_run ON logs (run_id, node);
+                CREATE TABLE executions (
+                    id INTEGER PRIMARY KEY,
+                    run_id TEXT NOT NULL,
+                    node INTEGER NOT NULL,
+                    parent INTEGER,
+                    depth INTEGER NOT NULL,
+                    position INTEGER,
+                    promptname TEXT NOT NULL,
+                    error TEXT,
+                    created TEXT NOT NULL
+                );
+                CREATE UNIQUE INDEX idx_executions_run ON executions (run_id, node);"
+            )?;
+        }
+
+        tx.pragma_update(None, "user_version", 4)?;
 
         tx.commit()?;
 
@@ -80,6 +102,48 @@ impl RusqliteStore {
 
 }
 
+const LOG_COLUMNS: &str = "promptname, provider, model, variant, `group`, prompt_tokens, completion_tokens,
+    result, success, time_taken, created, cache_key, run_id, node, cached";
+
+fn log_record(row: &Row) -> rusqlite::Result<LogRecord> {
+    Ok(
+        LogRecord {
+            promptname: row.get(0)?,
+            provider: row.get(1)?,
+            model: row.get(2)?,
+            variant: row.get(3)?,
+            group: row.get(4)?,
+            prompt_tokens: row.get(5)?,
+            completion_tokens: row.get(6)?,
+            result: row.get(7)?,
+            success: row.get(8)?,
+            time_taken: row.get(9)?,
+            created: row.get(10)?,
+            cache_key: row.get(11)?,
+            run_id: row.get(12)?,
+            node: row.get(13)?,
+            cached: row.get(14)?
+        }
+    )
+}
+
+const EXECUTION_COLUMNS: &str = "run_id, node, parent, depth, position, promptname, error, created";
+
+fn execution_record(row: &Row) -> rusqlite::Result<ExecutionRecord> {
+    Ok(
+        ExecutionRecord {
+            run_id: row.get(0)?,
+            node: row.get(1)?,
+            parent: row.get(2)?,
+            depth: row.get(3)?,
+            position: row.get(4)?,
+            promptname: row.get(5)?,
+            error: row.get(6)?,
+            created: row.get(7)?
+        }
+    )
+}
+
 impl StatsStore for RusqliteStore {
     fn log(&self, record: LogRecord) -> Result<(), LogError> {
         self.conn.lock().unwrap().execute(
@@ -95,8 +159,11 @@ impl StatsStore for RusqliteStore {
                 success,
                 time_taken,
                 created,
-                cache_key
-                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)", params![
+                cache_key,
+                run_id,
+                node,
+                cached
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)", params![
                 &record.promptname,
                 &record.provider,
                 &record.model,
@@ -108,7 +175,10 @@ impl StatsStore for RusqliteStore {
                 record.success,
                 record.time_taken,
                 &record.created.to_rfc3339(),
-                &record.cache_key
+                &record.cache_key,
+                &record.run_id,
+                record.node,
+                record.cached
             ]
         ).map_err(|e| LogError::GeneralError(e.to_string()))?;
 
@@ -117,46 +187,16 @@ impl StatsStore for RusqliteStore {
 
     fn cached(&self, cache_key: i64, ttl: u32) -> Result<Option<LogRecord>, FetchError> {
         let cutoff = (Utc::now() - Duration::seconds(ttl.into())).to_rfc3339();
-        let sql = String::from(
-            "SELECT
-                promptname,
-                provider,
-                model,
-                variant,
-                `group`,
-                prompt_tokens,
-                completion_tokens,
-                result,
-                success,
-                time_taken,
-                created,
-                cache_key
-            FROM logs WHERE cache_key = ?1 AND created > ?2 ORDER BY id DESC LIMIT 1
-        ");
+        let sql = format!(
+            "SELECT {LOG_COLUMNS} FROM logs WHERE cache_key = ?1 AND created > ?2 AND cached = 0
+            ORDER BY id DESC LIMIT 1");
 
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(&sql)
             .map_err(|err| FetchError::GeneralError(err.to_string()))?;
 
 
-        let result = stmt.query_one(params![cache_key, cutoff], |row| {
-            Ok(
-                LogRecord {
-                    promptname: row.get(0)?,
-                    provider: row.get(1)?,
-                    model: row.get(2)?,
-                    variant: row.get(3)?,
-                    group: row.get(4)?,
-                    prompt_tokens: row.get(5)?,
-                    completion_tokens: row.get(6)?,
-                    result: row.get(7)?,
-                    success: row.get(8)?,
-                    time_taken: row.get(9)?,
-                    created: row.get(10)?,
-                    cache_key: row.get(11)?
-                }
-            )
-        });
+        let result = stmt.query_one(params![cache_key, cutoff], log_record);
 
         match result {
             Ok(record) => Ok(Some(record)),
@@ -166,22 +206,7 @@ impl StatsStore for RusqliteStore {
     }
 
     fn records(&self, last: Option<u32>) -> Result<Vec<LogRecord>, FetchError> {
-        let mut sql = String::from(
-            "SELECT
-                promptname,
-                provider,
-                model,
-                variant,
-                `group`,
-                prompt_tokens,
-                completion_tokens,
-                result,
-                success,
-                time_taken,
-                created,
-                cache_key
-            FROM logs
-        ");
+        let mut sql = format!("SELECT {LOG_COLUMNS} FROM logs WHERE cached = 0");
 
         let mut params: Vec<String> = Vec::new();
 
@@ -193,25 +218,7 @@ impl StatsStore for RusqliteStore {
         let mut stmt = conn.prepare(&sql)
             .map_err(|err| FetchError::GeneralError(err.to_string()))?;
 
-        let records = stmt.query_map(
-            params_from_iter(params.iter()), |row| {
-            Ok(
-                LogRecord {
-                    promptname: row.get(0)?,
-                    provider: row.get(1)?,
-                    model: row.get(2)?,
-                    variant: row.get(3)?,
-                    group: row.get(4)?,
-                    prompt_tokens: row.get(5)?,
-                    completion_tokens: row.get(6)?,
-                    result: row.get(7)?,
-                    success: row.get(8)?,
-                    time_taken: row.get(9)?,
-                    created: row.get(10)?,
-                    cache_key: row.get(11)?
-                }
-            )
-        }).map_err(|err| FetchError::GeneralError(err.to_string()))?;
+        let records = stmt.query_map(params_from_iter(params.iter()), log_record).map_err(|err| FetchError::GeneralError(err.to_string()))?;
 
         //let result: Vec<SummaryItem> = records.filter_map(Result::ok).collect();
         let result: Result<Vec<_>, _> = records.collect();
@@ -219,6 +226,83 @@ impl StatsStore for RusqliteStore {
         result.map_err(|err| FetchError::GeneralError(err.to_string()))
     }
 
+    fn log_execution(&self, execution: ExecutionRecord) -> Result<(), LogError> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO executions (run_id, node, parent, depth, position, promptname, error, created)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)", params![
+                &execution.run_id,
+                execution.node,
+                execution.parent,
+                execution.depth,
+                execution.position,
+                &execution.promptname,
+                &execution.error,
+                &execution.created.to_rfc3339()
+            ]
+        ).map_err(|e| LogError::GeneralError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    fn log_execution_error(&self, run_id: &str, node: u32, error: &str) -> Result<(), LogError> {
+        self.conn.lock().unwrap().execute(
+            "UPDATE executions SET error = ?3 WHERE run_id = ?1 AND node = ?2",
+            params![run_id, node, error]
+        ).map_err(|e| LogError::GeneralError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    fn last_run_id(&self) -> Result<Option<String>, FetchError> {
+        let conn = self.conn.lock().unwrap();
+        let result = conn.query_one("SELECT run_id FROM executions ORDER BY id DESC LIMIT 1", [], |row| row.get(0));
+
+        match result {
+            Ok(run_id) => Ok(Some(run_id)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(err) => Err(FetchError::GeneralError(err.to_string()))
+        }
+    }
+
+    fn runs(&self, last: u32) -> Result<Vec<ExecutionRecord>, FetchError> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {EXECUTION_COLUMNS} FROM executions WHERE parent IS NULL ORDER BY id DESC LIMIT ?1"
+        )).map_err(|err| FetchError::GeneralError(err.to_string()))?;
+        stmt.query_map(params![last], execution_record)
+            .and_then(|runs| runs.collect::<Result<Vec<_>, _>>())
+            .map_err(|err| FetchError::GeneralError(err.to_string()))
+    }
+
+    fn trace(&self, run_id: &str) -> Result<Vec<TraceNode>, FetchError> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {EXECUTION_COLUMNS} FROM executions WHERE run_id = ?1 ORDER BY node"
+        )).map_err(|err| FetchError::GeneralError(err.to_string()))?;
+        let executions = stmt.query_map(params![run_id], execution_record)
+            .and_then(|executions| executions.collect::<Result<Vec<_>, _>>())
+            .map_err(|err| FetchError::GeneralError(err.to_string()))?;
+
+        let mut stmt = conn.prepare(&format!("SELECT {LOG_COLUMNS} FROM logs WHERE run_id = ?1 ORDER BY id"))
+            .map_err(|err| FetchError::GeneralError(err.to_string()))?;
+        let calls = stmt.query_map(params![run_id], log_record)
+            .and_then(|calls| calls.collect::<Result<Vec<_>, _>>())
+            .map_err(|err| FetchError::GeneralError(err.to_string()))?;
+
+        let mut nodes: Vec<TraceNode> = executions.into_iter()
+            .map(|execution| TraceNode { execution, calls: Vec::new() })
+            .collect();
+        for call in calls {
+            if let Some(node) = nodes.iter_mut().find(|node| Some(node.execution.node) == call.node) {
+                node.calls.push(call);
+            }
+        }
+
+        Ok(nodes)
+    }
+
     fn summary(&self,
         provider: Option<String>,
         model: Option<String>,
@@ -234,7 +318,7 @@ impl StatsStore for RusqliteStore {
                 SUM(prompt_tokens),
                 SUM(completion_tokens),
                 COALESCE(SUM(completion_tokens) * 1.0 / SUM(time_taken), 0)
-            FROM logs WHERE 1=1");
+            FROM logs WHERE cached = 0");
         let mut params: Vec<String> = Vec::new();
 
         let mut group_by: Vec<&'static str> = Vec::new();
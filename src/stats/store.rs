@@ -32,7 +32,32 @@ pub struct LogRecord {
     pub success: bool,
     pub time_taken: u32,
     pub created: DateTime<Utc>,
-    pub cache_key: Option<i64>
+    pub cache_key: Option<i64>,
+    // Execution of the run the request was made for
+    pub run_id: Option<String>,
+    pub node: Option<u32>,
+    // Answered from the cache, without a request
+    pub cached: bool
+}
+
+/// A prompt executed in a run, nested in the execution `parent`, if any.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutionRecord {
+    pub run_id: String,
+    pub node: u32,
+    pub parent: Option<u32>,
+    pub depth: u32,
+    // Order in which the parent started the nested prompt
+    pub position: Option<u32>,
+    pub promptname: String,
+    pub error: Option<String>,
+    pub created: DateTime<Utc>,
+}
+
+/// An execution of a run with the requests made for it.
+pub struct TraceNode {
+    pub execution: ExecutionRecord,
+    pub calls: Vec<LogRecord>,
 }
 
 #[derive(Debug)]
@@ -49,6 +74,12 @@ pub trait StatsStore: Send + Sync {
     fn log(&self, item: LogRecord) -> Result<(), LogError>;
     fn records(&self, last: Option<u32>) -> Result<Vec<LogRecord>, FetchError>;
     fn cached(&self, cache_key: i64, ttl: u32) -> Result<Option<LogRecord>, FetchError>;
+    fn log_execution(&self, execution: ExecutionRecord) -> Result<(), LogError>;
+    fn log_execution_error(&self, run_id: &str, node: u32, error: &str) -> Result<(), LogError>;
+    fn last_run_id(&self) -> Result<Option<String>, FetchError>;
+    // Top-level executions of the last runs, latest first
+    fn runs(&self, last: u32) -> Result<Vec<ExecutionRecord>, FetchError>;
+    fn trace(&self, run_id: &str) -> Result<Vec<TraceNode>, FetchError>;
     fn summary(&self,
         provider: Option<String>,
         model: Option<String>,
@@ -13,6 +13,9 @@ pub enum PromptFilesStorageError {
     #[error("Prompt not found at: {0}")]
     PromptNotFound(String),
 
+    #[error("Partial not found: {0}")]
+    PartialNotFound(String),
+
     #[error("Failed to write prompt file, reason: {0}")]
     Other(String),
 }
@@ -22,4 +25,18 @@ pub trait PromptFilesStorage: Send + Sync {
     fn exists(&self, identifier: &str) -> Option<String>;
     fn store(&self, identifier: &str, dotpromptdata: &str) -> Result<String, PromptFilesStorageError>;
     fn load(&self, identifier: &str) -> Result<(String, String), PromptFilesStorageError>;
+    fn list_partials(&self) -> Result<HashMap<String, String>, PromptFilesStorageError>;
+    fn load_partial(&self, identifier: &str) -> Result<(String, String), PromptFilesStorageError>;
+    /// Content of the test cases kept next to a prompt, if any.
+    fn load_tests(&self, identifier: &str) -> Result<Option<String>, PromptFilesStorageError>;
+
+    /// Contents of all partials, by name.
+    fn partials(&self) -> Result<HashMap<String, String>, PromptFilesStorageError> {
+        self.list_partials()?.into_keys()
+            .map(|name| {
+                let (_, content) = self.load_partial(&name)?;
+                Ok((name, content))
+            })
+            .collect()
+    }
 }
@@ -1,4 +1,4 @@
-use std::{collections::HashMap, fs, path::PathBuf};
+use std::{collections::HashMap, fs, path::{Path, PathBuf}};
 
 use crate::storage::{PromptFilesStorage, PromptFilesStorageError};
 
@@ -18,34 +18,51 @@ impl FileSystemPromptFilesStorage {
 
         self.root_dir.join(promptfilename)
     }
+
+    // Partials are looked up next to the prompt files first, then in the
+    // partials subdirectory.
+    fn resolve_partial(&self, identifier: &str) -> Option<PathBuf> {
+        let partialfilename: String = format!("{identifier}.partial");
+
+        [self.root_dir.join(&partialfilename), self.root_dir.join(PARTIALS_DIR).join(&partialfilename)]
+            .into_iter()
+            .find(|path| path.is_file())
+    }
 }
 
-impl PromptFilesStorage for FileSystemPromptFilesStorage {
+const PARTIALS_DIR: &str = "partials";
+const TESTS_EXTENSION: &str = "test.yaml";
 
-    fn list(&self) -> Result<HashMap<String, String>, PromptFilesStorageError> {
-        let mut result: HashMap<String, String> = HashMap::new();
+fn list_files(dir: &Path, extension: &str) -> Result<HashMap<String, String>, PromptFilesStorageError> {
+    let mut result: HashMap<String, String> = HashMap::new();
 
-        if ! fs::exists(&self.root_dir)? {
-            return Ok(result)
-        }
+    if ! fs::exists(dir)? {
+        return Ok(result)
+    }
 
-        let dir_entries = fs::read_dir(&self.root_dir)?;
+    let dir_entries = fs::read_dir(dir)?;
 
-        for entry in dir_entries {
-            let path = entry?.path();
+    for entry in dir_entries {
+        let path = entry?.path();
 
-            if path.is_file() &&
-                let Some(e) = path.extension() &&
-                e == "prompt" &&
-                let Some(promptname) = path.file_stem() {
+        if path.is_file() &&
+            let Some(e) = path.extension() &&
+            e == extension &&
+            let Some(name) = path.file_stem() {
 
-                result.insert(
-                    promptname.to_string_lossy().into_owned(),
-                    path.to_string_lossy().into_owned());
-            }
+            result.insert(
+                name.to_string_lossy().into_owned(),
+                path.to_string_lossy().into_owned());
         }
+    }
 
-        Ok(result)
+    Ok(result)
+}
+
+impl PromptFilesStorage for FileSystemPromptFilesStorage {
+
+    fn list(&self) -> Result<HashMap<String, String>, PromptFilesStorageError> {
+        list_files(&self.root_dir, "prompt")
     }
 
 
@@ -76,4 +93,72 @@ impl PromptFilesStorage for FileSystemPromptFilesStorage {
 
         Ok((filepath.to_string_lossy().into_owned(), data))
     }
+
+    fn list_partials(&self) -> Result<HashMap<String, String>, PromptFilesStorageError> {
+        let mut result = list_files(&self.root_dir.join(PARTIALS_DIR), "partial")?;
+        result.extend(list_files(&self.root_dir, "partial")?);
+
+        Ok(result)
+    }
+
+    fn load_partial(&self, identifier: &str) -> Result<(String, String), PromptFilesStorageError> {
+        let filepath = self.resolve_partial(identifier)
+            .ok_or(PromptFilesStorageError::PartialNotFound(identifier.to_string()))?;
+
+        let data = fs::read_to_string(&filepath)?;
+
+        Ok((filepath.to_string_lossy().into_owned(), data))
+    }
+
+    fn load_tests(&self, identifier: &str) -> Result<Option<String>, PromptFilesStorageError> {
+        let filepath = self.root_dir.join(format!("{identifier}.{TESTS_EXTENSION}"));
+        if !filepath.is_file() {
+            return Ok(None)
+        }
+
+        Ok(Some(fs::read_to_string(&filepath)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::storage::{promptfiles_fs::FileSystemPromptFilesStorage, PromptFilesStorage};
+
+    #[test]
+    fn test_partials() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("partials")).unwrap();
+        fs::write(dir.path().join("summarize.prompt"), "Summarize").unwrap();
+        fs::write(dir.path().join("code-only.partial"), "Only code").unwrap();
+        fs::write(dir.path().join("partials").join("polite.partial"), "Be polite").unwrap();
+
+        let storage = FileSystemPromptFilesStorage::new(dir.path().to_path_buf());
+
+        assert_eq!(storage.list().unwrap().into_keys().collect::<Vec<_>>(), vec!["summarize"]);
+
+        let mut partials = storage.partials().unwrap().into_iter().collect::<Vec<_>>();
+        partials.sort();
+        assert_eq!(partials, vec![
+            ("code-only".to_string(), "Only code".to_string()),
+            ("polite".to_string(), "Be polite".to_string()),
+        ]);
+
+        assert!(storage.exists("code-only").is_none());
+        assert!(storage.load_partial("missing").is_err());
+    }
+
+    #[test]
+    fn test_load_tests() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("summarize.prompt"), "Summarize").unwrap();
+        fs::write(dir.path().join("summarize.test.yaml"), "- inputs: {}").unwrap();
+
+        let storage = FileSystemPromptFilesStorage::new(dir.path().to_path_buf());
+
+        assert_eq!(storage.list().unwrap().into_keys().collect::<Vec<_>>(), vec!["summarize"]);
+        assert_eq!(storage.load_tests("summarize").unwrap().as_deref(), Some("- inputs: {}"));
+        assert_eq!(storage.load_tests("other").unwrap(), None);
+    }
 }
@@ -5,7 +5,21 @@ use crate::storage::{PromptFilesStorage, PromptFilesStorageError};
 
 #[derive(Default)]
 pub struct InMemoryPromptFilesStorage {
-    storage: Mutex<HashMap<String, String>>
+    storage: Mutex<HashMap<String, String>>,
+    partials: Mutex<HashMap<String, String>>,
+    tests: Mutex<HashMap<String, String>>
+}
+
+impl InMemoryPromptFilesStorage {
+    pub fn store_partial(&self, identifier: &str, partial: &str) {
+        let mut partials = self.partials.lock().unwrap();
+        partials.insert(identifier.to_string(), partial.to_string());
+    }
+
+    pub fn store_tests(&self, identifier: &str, tests: &str) {
+        let mut storage = self.tests.lock().unwrap();
+        storage.insert(identifier.to_string(), tests.to_string());
+    }
 }
 
 impl PromptFilesStorage for InMemoryPromptFilesStorage {
@@ -40,4 +54,21 @@ impl PromptFilesStorage for InMemoryPromptFilesStorage {
             Err(PromptFilesStorageError::Other(String::from("No such identifier")))
         }
     }
+
+    fn list_partials(&self) -> Result<HashMap<String, String>, PromptFilesStorageError> {
+        let partials = self.partials.lock().unwrap();
+        Ok(partials.keys().map(|key| (key.clone(), key.clone())).collect())
+    }
+
+    fn load_partial(&self, identifier: &str) -> Result<(String, String), PromptFilesStorageError> {
+        let partials = self.partials.lock().unwrap();
+        partials.get(identifier)
+            .map(|partial| (identifier.to_string(), partial.clone()))
+            .ok_or(PromptFilesStorageError::PartialNotFound(identifier.to_string()))
+    }
+
+    fn load_tests(&self, identifier: &str) -> Result<Option<String>, PromptFilesStorageError> {
+        let tests = self.tests.lock().unwrap();
+        Ok(tests.get(identifier).cloned())
+    }
 }